@@ -3,7 +3,7 @@ use crate::intcode;
 fn run_noun_verb(vm: &mut intcode::VM, noun: i128, verb: i128) -> i128 {
     vm.memory[1] = noun;
     vm.memory[2] = verb;
-    vm.run(&mut vec![]);
+    vm.run(&mut &[][..]).unwrap();
     vm.memory[0]
 }
 
@@ -28,33 +28,34 @@ mod test {
     use std::error::Error;
 
     use crate::intcode;
+    use crate::intcode::symbolic::Explorer;
     use crate::util;
 
     #[test]
     fn test_02_ex1() {
         let mut vm = intcode::VM::new(&[1, 0, 0, 0, 99]);
-        vm.run(&mut vec![]);
+        vm.run(&mut &[][..]).unwrap();
         assert_eq!(vm.memory, &[2, 0, 0, 0, 99]);
     }
 
     #[test]
     fn test_02_ex2() {
         let mut vm = intcode::VM::new(&[2, 3, 0, 3, 99]);
-        vm.run(&mut vec![]);
+        vm.run(&mut &[][..]).unwrap();
         assert_eq!(vm.memory, &[2, 3, 0, 6, 99]);
     }
 
     #[test]
     fn test_02_ex3() {
         let mut vm = intcode::VM::new(&[2, 4, 4, 5, 99, 0]);
-        vm.run(&mut vec![]);
+        vm.run(&mut &[][..]).unwrap();
         assert_eq!(vm.memory, &[2, 4, 4, 5, 99, 9801]);
     }
 
     #[test]
     fn test_02_ex4() {
         let mut vm = intcode::VM::new(&[1, 1, 1, 4, 99, 5, 6, 0, 99]);
-        vm.run(&mut vec![]);
+        vm.run(&mut &[][..]).unwrap();
         assert_eq!(vm.memory, &[30, 1, 1, 4, 2, 5, 6, 0, 99]);
     }
 
@@ -65,4 +66,17 @@ mod test {
         assert_eq!(super::day02b(&vm), 5335);
         Ok(())
     }
+
+    #[test]
+    fn test_02_symbolic() -> Result<(), Box<dyn Error>> {
+        // memory[0] ends up linear in noun and verb, so the solver finds them without trying pairs
+        let vm = util::get_parsed_line::<intcode::VM>("input/day02.txt")?;
+        let mut explorer = Explorer::new(&vm.memory.to_vec());
+        explorer.symbolic_cell(1, 0, 99);
+        explorer.symbolic_cell(2, 0, 99);
+        let paths = explorer.explore();
+        let solution = explorer.solve_cell(&paths, 0, 19690720).unwrap();
+        assert_eq!(solution.cells[0].1 * 100 + solution.cells[1].1, super::day02b(&vm));
+        Ok(())
+    }
 }
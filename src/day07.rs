@@ -1,54 +1,12 @@
-use std::cell::RefCell;
-
 use crate::intcode;
+use crate::intcode::amplifier::AmplifierChain;
 
 pub fn day07a(vm: &intcode::VM) -> i128 {
-    permutohedron::Heap::new(&mut [0, 1, 2, 3, 4])
-        .map(|phases| phases
-             .iter()
-             .scan(0, |state, &phase| {
-                let mut vm = vm.clone();
-                let output = vm.run(&mut vec![phase, *state]);
-                *state = *output.last().unwrap();
-                Some(*state)
-             })
-             .last()
-             .unwrap()
-        )
-        .max()
-        .unwrap()
+    AmplifierChain::new(vm, &[0, 1, 2, 3, 4]).search(0).unwrap().expect("no output").1
 }
 
 pub fn day07b(vm: &intcode::VM) -> i128 {
-    let mut max = 0;
-    for phases in permutohedron::Heap::new(&mut [5, 6, 7, 8, 9]) {
-        let st = &mut [intcode::Status::Suspended, intcode::Status::Suspended, intcode::Status::Suspended, intcode::Status::Suspended, intcode::Status::Suspended];
-        let b = [RefCell::new(vec![]), RefCell::new(vec![]), RefCell::new(vec![]), RefCell::new(vec![]), RefCell::new(vec![])];
-        let vm = &mut [vm.clone(), vm.clone(), vm.clone(), vm.clone(), vm.clone()];
-
-        for i in 0..=4 {
-            b[i].borrow_mut().push(phases[i]);
-        }
-        b[0].borrow_mut().push(0);
-
-        loop {
-            if st.iter().all(|s| matches!(s, intcode::Status::Halted)) {
-                let v = *b[0].borrow().last().unwrap();
-                if v > max {
-                    max = v;
-                }
-                break;
-            }
-            for i in 0..=4 {
-                if let intcode::Status::Halted = st[i] {
-                    continue
-                }
-                st[i] = vm[i].step(&mut b[i].borrow_mut(), &mut b[(i+1)%5].borrow_mut());
-                //println!("Status:{:?}; machine:{:?}, IP:{:?}, buffers:{:?}", st[i], i, ip[i], b);
-            }
-        }
-    }
-    max
+    AmplifierChain::new(vm, &[5, 6, 7, 8, 9]).feedback().search(0).unwrap().expect("no output").1
 }
 
 #[cfg(test)]
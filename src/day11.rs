@@ -1,4 +1,4 @@
-use std::collections::{HashMap, HashSet};
+use std::collections::{HashMap, HashSet, VecDeque};
 use std::error::Error;
 use std::io::Write;
 
@@ -13,7 +13,7 @@ static TURN_CW: Complex<i32> = Complex { re: 0, im: 1 };
 
 struct PaintRobot {
     vm: intcode::VM,
-    out: Vec<i128>,
+    out: VecDeque<i128>,
     state: intcode::Status,
     grid: HashMap<Complex<i32>, i128>,
     pos: Complex<i32>,
@@ -24,7 +24,7 @@ impl PaintRobot {
     fn new(vm: &intcode::VM) -> Self {
         PaintRobot {
             vm: vm.clone(),
-            out: Vec::new(),
+            out: VecDeque::new(),
             state: intcode::Status::Suspended,
             grid: HashMap::new(),
             pos: Complex::new(0, 0),
@@ -50,13 +50,14 @@ impl Iterator for PaintRobot {
                 return None;
             }
 
-            // get more output from VM
-            let mut input = Vec::<i128>::new();
+            // get more output from VM; the camera sees the color of the current position
+            let color = *self.grid.get(&self.pos).unwrap_or(&0);
+            let mut camera = || Some(color);
             loop {
-                self.state = self.vm.step(&mut input, &mut self.out);
+                self.state = self.vm.step(&mut camera, &mut self.out).unwrap();
                 match self.state {
                     intcode::Status::Halted     => break,
-                    intcode::Status::Blocked    => input.push(*self.grid.get(&self.pos).unwrap_or(&0)),
+                    intcode::Status::Blocked    => unreachable!(),
                     intcode::Status::Suspended  => if self.out.len() >= 2 { break },
                 }
             }
@@ -68,11 +69,11 @@ impl Iterator for PaintRobot {
         }
 
         // update grid with first output
-        let new_color = self.out.remove(0);
+        let new_color = self.out.pop_front().unwrap();
         *self.grid.entry(self.pos).or_insert(0) = new_color;
 
         // update direction and position with second output
-        let turn = self.out.remove(0);
+        let turn = self.out.pop_front().unwrap();
         self.dir *= if turn == 0 { TURN_CCW } else { TURN_CW };
         self.pos += self.dir;
 
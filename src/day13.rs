@@ -1,3 +1,4 @@
+use std::collections::VecDeque;
 use std::io::Write;
 use std::error::Error;
 
@@ -58,7 +59,7 @@ enum Output {
 
 struct Game {
     vm: intcode::VM,
-    out: Vec::<i128>,
+    out: VecDeque<i128>,
     state: intcode::Status,
     ball_x: Option<i128>,
     paddle_x: Option<i128>,
@@ -68,7 +69,7 @@ impl Game {
     fn new(vm: &intcode::VM) -> Game {
         Game {
             vm: vm.clone(),
-            out: vec![],
+            out: VecDeque::new(),
             state: intcode::Status::Suspended,
             ball_x: None,
             paddle_x: None,
@@ -85,19 +86,21 @@ impl Iterator for Game {
                 return None;
             }
 
-            let mut input = vec![];
+            // the joystick follows the ball
+            let tilt = (self.ball_x.unwrap_or(0) - self.paddle_x.unwrap_or(0)).signum();
+            let mut joystick = || Some(tilt);
             loop {
-                match self.vm.step(&mut input, &mut self.out) {
+                match self.vm.step(&mut joystick, &mut self.out).unwrap() {
                     intcode::Status::Suspended  => { if self.out.len() >= 3 { break } },
                     intcode::Status::Halted     => { if self.out.len() >= 3 { break } else { return None } },
-                    intcode::Status::Blocked    => { input.push((self.ball_x.unwrap_or(0) - self.paddle_x.unwrap_or(0)).signum()); },
+                    intcode::Status::Blocked    => unreachable!(),
                 }
             }
         }
 
-        let x = self.out.remove(0);
-        let y = self.out.remove(0);
-        let v = self.out.remove(0);
+        let x = self.out.pop_front().unwrap();
+        let y = self.out.pop_front().unwrap();
+        let v = self.out.pop_front().unwrap();
 
         let result = if x == -1 && y == 0 {
             Output::ScoreUpdate(v)
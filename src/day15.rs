@@ -254,28 +254,25 @@ impl Iterator for RepairDroid {
             self.plan.pop();
         }
 
-        let mut input = Vec::<i128>::new();
-        let mut output = Vec::<i128>::new();
-        loop {
-            match self.vm.step(&mut input, &mut output) {
-
-                intcode::Status::Halted => { break; }
-
-                intcode::Status::Blocked => { self.step = self.plan.pop().unwrap() - self.grid.pos;
-                                              input.push(*DIRECTIONS.get(&self.step).unwrap()); }
-
-                intcode::Status::Suspended => { let x = self.grid.pos + self.step;
-                                                match output.remove(0) {
-                                                    0 => { self.grid[x] = Tile::Wall }
-                                                    1 => { self.grid[x] = Tile::Floor  ; self.grid.pos += self.step }
-                                                    2 => { self.grid[x] = Tile::Target ; self.grid.pos += self.step ; self.target = Some(self.grid.pos) }
-                                                    x => { panic!("HAVE OUTPUT {}", x) }
-                                                };
-                                                return Some(self.grid.to_string()); }
-            }
+        // each move command yields exactly one status reply
+        self.step = self.plan.pop().unwrap() - self.grid.pos;
+        let mut input = VecDeque::from(vec![*DIRECTIONS.get(&self.step).unwrap()]);
+        let mut output = VecDeque::new();
+        match self.vm.step(&mut input, &mut output).unwrap() {
+
+            intcode::Status::Halted => { None }
+
+            intcode::Status::Blocked => { panic!("droid wants a second command") }
+
+            intcode::Status::Suspended => { let x = self.grid.pos + self.step;
+                                            match output.pop_front().unwrap() {
+                                                0 => { self.grid[x] = Tile::Wall }
+                                                1 => { self.grid[x] = Tile::Floor  ; self.grid.pos += self.step }
+                                                2 => { self.grid[x] = Tile::Target ; self.grid.pos += self.step ; self.target = Some(self.grid.pos) }
+                                                x => { panic!("HAVE OUTPUT {}", x) }
+                                            };
+                                            Some(self.grid.to_string()) }
         }
-
-        None
     }
 }
 
@@ -5,6 +5,7 @@ use std::io::Write;
 use num_complex::Complex;
 
 use crate::intcode;
+use crate::intcode::ascii::{self, AsciiEvent, AsciiVm};
 
 //
 // type definitions and constants
@@ -231,19 +232,24 @@ fn subroutines(program: &[Action], subs: [Vec<Action>; 4], first_empty: usize) -
     None
 }
 
+// answers the robot's prompts for the main routine, the three functions and the video feed
 fn run_robot(mut vm: intcode::VM, subs: &[Vec<Action>; 4]) -> i128 {
-    let mut subs = subs
-        .iter()
-        .flat_map(|sub| display_list(&sub)
-                            .bytes()
-                            .map(|b| b as i128)
-                            .chain(vec![10])
-                            .collect::<Vec<_>>())
-        .chain(vec![110, 10])
-        .collect::<Vec<_>>();
     vm.memory[0] = 2;
-    let output = vm.run(&mut subs);
-    *output.last().unwrap()
+    let mut robot = AsciiVm::new(vm);
+    for (prompt, sub) in ["^Main:", "^Function A:", "^Function B:", "^Function C:"].iter().zip(subs) {
+        robot.respond(prompt, &display_list(sub)).unwrap();
+    }
+    robot.respond("^Continuous video feed?", "n").unwrap();
+    match robot.read_all().unwrap().pop() {
+        Some(AsciiEvent::Value(dust)) => dust,
+        wtf                           => panic!("robot did not report dust: {:?}", wtf),
+    }
+}
+
+// what the camera sees
+fn camera(vm: &intcode::VM) -> String {
+    let events = AsciiVm::new(vm.clone()).read_all().unwrap();
+    ascii::transcript(&events)
 }
 
 fn display_list<T: std::fmt::Display>(list: &[T]) -> String {
@@ -251,17 +257,13 @@ fn display_list<T: std::fmt::Display>(list: &[T]) -> String {
 }
 
 pub fn day17a(vm: &intcode::VM) -> i32 {
-    let output = vm.clone().run(&mut vec![]);
-    let bytes = output.iter().map(|&w| w as u8).collect::<Vec<u8>>();
-    let robot = CleaningRobot::new(&bytes);
+    let robot = CleaningRobot::new(camera(vm).as_bytes());
     robot.alignment_parameter()
 }
 
 
 pub fn day17b(vm: &intcode::VM) -> i128 {
-    let output = vm.clone().run(&mut vec![]);
-    let bytes = output.iter().map(|&w| w as u8).collect::<Vec<u8>>();
-    let robot = CleaningRobot::new(&bytes);
+    let robot = CleaningRobot::new(camera(vm).as_bytes());
     let route = robot.walk().collect::<Vec<Action>>();
     let prog = compressed(&route);
     let subs = subroutines(&prog, [vec![], vec![], vec![], vec![]], 1).unwrap();
@@ -271,11 +273,10 @@ pub fn day17b(vm: &intcode::VM) -> i128 {
 pub fn day17_main(vm: &intcode::VM) -> Result<(), Box<dyn std::error::Error>> {
     let mut stdout = std::io::stdout();
 
-    let output = vm.clone().run(&mut vec![]);
-    let bytes = output.iter().map(|&w| w as u8).collect::<Vec<u8>>();
-    stdout.write_all(&bytes)?;
+    let view = camera(vm);
+    stdout.write_all(view.as_bytes())?;
 
-    let robot = CleaningRobot::new(&bytes);
+    let robot = CleaningRobot::new(view.as_bytes());
     writeln!(stdout, "*** ROBOT START: POSITION={} DIRECTION={}", robot.pos, robot.dir)?;
 
     let answer = robot.alignment_parameter();
@@ -1,10 +1,11 @@
+use crate::intcode::io::Iter;
 use crate::intcode::VM;
 
 pub fn day19a(vm: &VM) -> i128 {
-    let mut input = (0..50).flat_map(|y| (0..50).map(move |x| vec![x, y])).flatten().collect::<Vec<_>>();
+    let mut input = Iter((0..50).flat_map(|y| (0..50).flat_map(move |x| vec![x, y])).peekable());
     let mut output = vec![];
-    while ! input.is_empty() {
-        vm.clone().run_with(&mut input, &mut output);
+    while input.0.peek().is_some() {
+        vm.clone().run_with(&mut input, &mut output).unwrap();
     }
     output.iter().sum()
 }
@@ -13,10 +14,10 @@ pub fn day19b(vm: &VM) -> i128 {
     let mut x = 0;
     let mut y = 100;
     loop {
-        while vm.clone().run(&mut vec![x, y]) != [1] {
+        while vm.clone().run(&mut &[x, y][..]).unwrap() != [1] {
             x += 1;
         }
-        if vm.clone().run(&mut vec![x + 99, y - 99]) == [1] {
+        if vm.clone().run(&mut &[x + 99, y - 99][..]).unwrap() == [1] {
             break
         }
         y += 1;
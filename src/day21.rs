@@ -1,19 +1,19 @@
 use crate::intcode;
+use crate::intcode::ascii::{self, AsciiEvent, AsciiVm};
 
 fn survey_hull(vm: &intcode::VM, program: &[&str]) -> Result<i128, String> {
-    let mut input = program
-        .join("\n")
-        .bytes()
-        .chain(std::iter::once(10))
-        .map(|b| b as i128)
-        .collect();
-
-    let output = &vm.clone().run(&mut input);
+    let mut droid = AsciiVm::new(vm.clone());
+    droid.expect("^Input instructions:").map_err(|e| e.to_string())?;
+    for line in program {
+        droid.send_line(line);
+    }
+    let events = droid.read_all().map_err(|e| e.to_string())?;
 
-    match output.last() {
-        Some(10) => Err(output.iter().map(|b| *b as u8 as char).collect()),
-        Some(x)  => Ok(*x),
-        None     => Err("program produced no output".to_owned()),
+    // the droid only reports the hull damage if it made it across
+    match events.last() {
+        Some(AsciiEvent::Value(x)) => Ok(*x),
+        Some(_)                    => Err(ascii::transcript(&events)),
+        None                       => Err("program produced no output".to_owned()),
     }
 }
 
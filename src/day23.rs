@@ -1,99 +1,51 @@
-use std::cell::RefCell;
-use std::collections::VecDeque;
-
 use crate::intcode;
+use crate::intcode::packet::{Action, Config, Nat, Packet, PacketNetwork};
 
 //
-// struct Machine
-//
-
-struct Machine {
-    vm: intcode::VM,
-    i: Vec<i128>,
-    o: Vec<i128>,
-}
-
-impl Machine {
-    fn new(vm: &intcode::VM, addr: usize) -> Self {
-        Self {
-            vm: vm.clone(),
-            i: vec![addr as i128],
-            o: vec![]
-        }
-    }
-
-    fn step(&mut self) -> intcode::Status {
-        self.vm.step(&mut self.i, &mut self.o)
-    }
-}
-
-//
-// struct CategorySix
+// struct FirstPacket -- stops at the first packet for the NAT
 //
 
-struct CategorySix {
-    machines: Vec<RefCell<Machine>>,
-    runnable: RefCell<VecDeque<usize>>,
+#[derive(Default)]
+pub struct FirstPacket {
+    pub y: Option<i128>,
 }
 
-impl CategorySix {
-    fn new(vm: &intcode::VM, count: usize) -> Self {
-        Self {
-            machines: (0..count)
-                .map(|i| RefCell::new(Machine::new(&vm, i)))
-                .collect(),
-            runnable: RefCell::new((0..count).collect()),
-        }
+impl Nat for FirstPacket {
+    fn receive(&mut self, packet: &Packet) -> Action {
+        self.y = Some(packet.y);
+        Action::Stop
     }
 
-    fn send(&self, n: i128, x: i128, y: i128) {
-        self.machines[n as usize].borrow_mut().i.extend(&[x, y]);
-        self.runnable.borrow_mut().push_back(n as usize);
+    fn idle(&mut self) -> Action {
+        Action::Continue
     }
 }
 
 //
-// struct CategorySixIterator
+// struct Monitor -- wakes the network with the last packet, until it sends the same Y twice
 //
 
-struct CategorySixIterator<'a> {
-    network: &'a CategorySix,
+#[derive(Default)]
+pub struct Monitor {
+    last: Option<(i128, i128)>,
+    sent: Option<i128>,
+    pub y: Option<i128>,
 }
 
-impl<'a> CategorySixIterator<'a> {
-    fn new(network: &'a CategorySix) -> Self {
-        Self {
-            network,
-        }
+impl Nat for Monitor {
+    fn receive(&mut self, packet: &Packet) -> Action {
+        self.last = Some((packet.x, packet.y));
+        Action::Continue
     }
-}
 
-impl Iterator for CategorySixIterator<'_> {
-    type Item = Option<(i128, i128, i128)>;
-
-    fn next(&mut self) -> Option<Self::Item> {
-        while let Some(i) = self.network.runnable.borrow_mut().pop_front() {
-            if self.network.machines[i].borrow().i.is_empty() {
-                self.network.machines[i].borrow_mut().i.push(-1);
-            }
-            let status = self.network.machines[i].borrow_mut().step();
-            match status {
-                intcode::Status::Halted => {
-                    unreachable!();
-                },
-                intcode::Status::Blocked => {
-                    continue;
-                },
-                intcode::Status::Suspended if self.network.machines[i].borrow().o.len() >= 3 => {
-                    let a = &mut self.network.machines[i].borrow_mut().o;
-                    return Some(Some((a.remove(0), a.remove(0), a.remove(0))));
-                },
-                intcode::Status::Suspended => {
-                    continue;
-                },
-            }
+    fn idle(&mut self) -> Action {
+        let (x, y) = self.last.expect("all VMs idle and no broadcast seen yet");
+        if self.sent == Some(y) {
+            self.y = Some(y);
+            return Action::Stop;
         }
-        Some(None)
+        self.sent = Some(y);
+        Action::Send(0, x, y)
     }
 }
 
@@ -102,56 +54,15 @@ impl Iterator for CategorySixIterator<'_> {
 //
 
 pub fn day23a(vm: &intcode::VM) -> i128 {
-    let network = CategorySix::new(vm, 50);
-    for msg in CategorySixIterator::new(&network) {
-        match msg {
-            Some((i, _, y)) if i == 255 => return y,
-            Some((i, x, y)) => network.send(i, x, y),
-            None => (0..50).for_each(|i| network.runnable.borrow_mut().push_back(i)),
-        }
-    }
-    unreachable!()
+    let mut nat = FirstPacket::default();
+    PacketNetwork::new(vm, Config::default()).run(&mut nat).unwrap();
+    nat.y.expect("network halted without sending anything to the NAT")
 }
 
 pub fn day23b(vm: &intcode::VM) -> i128 {
-    let network = CategorySix::new(vm, 50);
-    let mut last = None;
-    let mut prev = None;
-    let mut idle = 0;
-    for msg in CategorySixIterator::new(&network) {
-        match msg {
-            Some((i, x, y)) if i == 255 => {
-                last = Some((x, y));
-                idle = 0;
-            },
-            Some((i, x, y)) => {
-                network.send(i, x, y);
-                idle = 0;
-            },
-            None => {
-                idle += 1;
-                if idle > 100 {
-                    if let Some((x, y)) = last {
-                        if Some(y) == prev {
-                            return y;
-                        }
-                        else {
-                            network.send(0, x, y);
-                            prev = Some(y);
-                            idle = 0;
-                        }
-                    }
-                    else {
-                        panic!("all VMs idle and no broadcast seen yet")
-                    }
-                }
-                else {
-                    (0..50).for_each(|i| network.runnable.borrow_mut().push_back(i));
-                }
-            },
-        }
-    }
-    unreachable!()
+    let mut nat = Monitor::default();
+    PacketNetwork::new(vm, Config::default()).run(&mut nat).unwrap();
+    nat.y.expect("network halted before the NAT saw a repeat")
 }
 
 //
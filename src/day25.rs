@@ -1,6 +1,381 @@
-use crate::intcode;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::io::{BufRead, Write};
 
-pub fn day25_main(vm: &intcode::VM) -> Result<(), Box<dyn std::error::Error>> {
-    vm.clone().run_stdio();
+use regex::Regex;
+
+use crate::intcode::{IntcodeOutput, VM, VmError};
+use crate::intcode::ascii::{self, AsciiEvent, AsciiVm};
+use crate::intcode::io::Bytes;
+use crate::intcode::snapshot::Snapshot;
+
+lazy_static! {
+    static ref ROOM: Regex = Regex::new("^== (.+) ==$").unwrap();
+    static ref PASSWORD: Regex = Regex::new(r"typing (\d+) on the keypad").unwrap();
+}
+
+//
+// struct Session -- the game plus checkpoints, played one command at a time
+//
+
+#[derive(Debug, thiserror::Error)]
+pub enum SessionError {
+    #[error(transparent)]
+    Vm(#[from] VmError),
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+    #[error("Not in a room yet")]
+    NoRoom,
+    #[error("No checkpoint in {0:?}")]
+    NoCheckpoint(String),
+}
+
+pub struct Session {
+    game: AsciiVm,
+    // the last room the game described
+    room: Option<String>,
+    checkpoints: HashMap<String, VM>,
+}
+
+impl Session {
+    pub fn new(snapshot: &Snapshot) -> Self {
+        let mut game = AsciiVm::new(snapshot.vm.clone());
+        game.input.extend(&snapshot.input);
+        game.put_back(&snapshot.output);
+        Session { game, room: None, checkpoints: HashMap::new() }
+    }
+
+    // the game with the input it has not read and the output not read from it
+    fn snapshot(&self) -> Snapshot {
+        Snapshot::new(&self.game.vm, &self.game.input.iter().copied().collect::<Vec<_>>(), &self.game.unread())
+    }
+
+    pub fn is_over(&self) -> bool {
+        self.game.is_halted()
+    }
+
+    // runs the game until it wants the next command, and returns what it printed
+    pub fn output(&mut self) -> Result<String, SessionError> {
+        let events = self.game.read_all()?;
+        for event in &events {
+            if let AsciiEvent::Line(line) = event {
+                if let Some(caps) = ROOM.captures(line) {
+                    self.room = Some(caps[1].to_owned());
+                }
+            }
+        }
+        Ok(ascii::transcript(&events))
+    }
+
+    // Runs a game command, or one of these:
+    //
+    //     save <file>      write a snapshot, to be resumed with `25 --resume <file>`
+    //     checkpoint       remember the current state under the name of the room
+    //     restore <room>   go back to the checkpoint in that room
+    //
+    // and returns what was printed in response.
+    pub fn command(&mut self, line: &str) -> Result<String, SessionError> {
+        let line = line.trim();
+        if let Some(filename) = line.strip_prefix("save ") {
+            self.snapshot().save(filename.trim())?;
+            return Ok(format!("saved to {}\n", filename.trim()));
+        }
+        if line == "checkpoint" {
+            let room = self.room.clone().ok_or(SessionError::NoRoom)?;
+            self.checkpoints.insert(room.clone(), self.game.vm.clone());
+            return Ok(format!("checkpoint in {}\n", room));
+        }
+        if let Some(room) = line.strip_prefix("restore ") {
+            let vm = self.checkpoints.get(room.trim()).ok_or_else(|| SessionError::NoCheckpoint(room.trim().to_owned()))?;
+            self.game = AsciiVm::new(vm.clone());
+            self.room = Some(room.trim().to_owned());
+            return Ok(format!("back in {}\n", room.trim()));
+        }
+        self.game.send_line(line);
+        self.output()
+    }
+
+    // plays a script from the start, and returns the transcript with the commands in it
+    pub fn play(&mut self, commands: &[String]) -> Result<String, SessionError> {
+        let mut transcript = self.output()?;
+        for command in commands {
+            transcript += &format!("{}\n", command);
+            transcript += &self.command(command)?;
+        }
+        Ok(transcript)
+    }
+}
+
+// One command per line. Blank lines and lines starting with '#' are skipped.
+pub fn parse_script(text: &str) -> Vec<String> {
+    text.lines()
+        .map(|line| line.trim())
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(|line| line.to_owned())
+        .collect()
+}
+
+//
+// struct Room -- a room as the game describes it
+//
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Room {
+    pub name: String,
+    pub doors: Vec<String>,
+    pub items: Vec<String>,
+}
+
+impl Room {
+    // the last room described in the text, which is the checkpoint after being sent back from the floor
+    pub fn parse(text: &str) -> Option<Room> {
+        let mut room: Option<Room> = None;
+        let mut list = "";
+        for line in text.lines() {
+            if let Some(caps) = ROOM.captures(line) {
+                room = Some(Room { name: caps[1].to_owned(), ..Room::default() });
+                continue;
+            }
+            let room = match &mut room {
+                Some(room) => room,
+                None       => continue,
+            };
+            match (list, line.strip_prefix("- ")) {
+                ("Doors here lead:", Some(door)) => room.doors.push(door.to_owned()),
+                ("Items here:", Some(item))      => room.items.push(item.to_owned()),
+                _                                => list = line,
+            }
+        }
+        room
+    }
+}
+
+//
+// solver -- explores the ship, then tries sets of items on the pressure-sensitive floor
+//
+
+const CHECKPOINT: &str = "Security Checkpoint";
+
+// Taking some items makes the game print forever. No room takes this many lines to describe.
+const MAX_LINES: usize = 100;
+
+fn back(door: &str) -> &'static str {
+    match door {
+        "north" => "south",
+        "south" => "north",
+        "east"  => "west",
+        "west"  => "east",
+        wtf     => panic!("not a door: {:?}", wtf),
+    }
+}
+
+// sends a command and returns what the game printed, or None if it would not stop printing
+fn send(game: &mut AsciiVm, command: &str) -> Result<Option<String>, VmError> {
+    game.send_line(command);
+    let mut events = vec![];
+    while let Some(event) = game.read()? {
+        if events.len() == MAX_LINES {
+            return Ok(None);
+        }
+        events.push(event);
+    }
+    Ok(Some(ascii::transcript(&events)))
+}
+
+struct Ship {
+    // every room in the order found, with the game as it was on entering and the doors taken from the start
+    rooms: Vec<(Room, AsciiVm, Vec<String>)>,
+    // the door from the checkpoint to the floor
+    floor: String,
+}
+
+fn explore(vm: &VM) -> Result<Ship, VmError> {
+    let mut game = AsciiVm::new(vm.clone());
+    let start = Room::parse(&ascii::transcript(&game.read_all()?)).expect("no room at the start");
+    let mut seen = HashSet::new();
+    seen.insert(start.name.clone());
+    let mut queue = VecDeque::from(vec![(start, game, vec![])]);
+    let mut rooms = vec![];
+    let mut floor = None;
+    while let Some((room, game, path)) = queue.pop_front() {
+        for door in &room.doors {
+            let mut next = game.clone();
+            match send(&mut next, door)?.as_deref().and_then(Room::parse) {
+                // the floor throws the droid back out
+                Some(found) if found.name == room.name      => floor = Some(door.clone()),
+                Some(found) if seen.insert(found.name.clone()) => queue.push_back((found, next, [&path[..], std::slice::from_ref(door)].concat())),
+                _                                            => (),
+            }
+        }
+        rooms.push((room, game, path));
+    }
+    Ok(Ship { rooms, floor: floor.expect("no pressure-sensitive floor") })
+}
+
+// whether the droid can still walk out of the room after taking the item
+fn is_safe(game: &AsciiVm, room: &Room, item: &str) -> Result<bool, VmError> {
+    let mut game = game.clone();
+    if send(&mut game, &format!("take {}", item))?.is_none() || game.is_halted() {
+        return Ok(false);
+    }
+    let text = send(&mut game, &room.doors[0])?;
+    Ok(!game.is_halted() && text.as_deref().and_then(Room::parse).is_some())
+}
+
+fn solve(vm: &VM) -> Result<Option<i128>, VmError> {
+    let ship = explore(vm)?;
+
+    // fetch every safe item, going back to the start each time
+    let mut game = ship.rooms[0].1.clone();
+    let mut items = vec![];
+    for (room, state, path) in &ship.rooms {
+        for item in &room.items {
+            if !is_safe(state, room, item)? {
+                continue;
+            }
+            for door in path {
+                send(&mut game, door)?;
+            }
+            send(&mut game, &format!("take {}", item))?;
+            for door in path.iter().rev() {
+                send(&mut game, back(door))?;
+            }
+            items.push(item.clone());
+        }
+    }
+
+    let (_, _, path) = ship.rooms.iter().find(|(room, _, _)| room.name == CHECKPOINT).expect("no security checkpoint");
+    for door in path {
+        send(&mut game, door)?;
+    }
+    for set in 0..1_u32 << items.len() {
+        let mut attempt = game.clone();
+        for (i, item) in items.iter().enumerate() {
+            if set & 1 << i == 0 {
+                send(&mut attempt, &format!("drop {}", item))?;
+            }
+        }
+        if let Some(caps) = send(&mut attempt, &ship.floor)?.as_deref().and_then(|text| PASSWORD.captures(text)) {
+            return Ok(caps[1].parse().ok());
+        }
+    }
+    Ok(None)
+}
+
+pub fn day25a(vm: &VM) -> i128 {
+    solve(vm).unwrap().expect("no set of items gets through the floor")
+}
+
+// Plays the adventure on stdin/stdout, after replaying the script. Every command that worked, from
+// the script or typed in, is appended to the recording, so that it can be replayed later.
+pub fn day25_main(snapshot: &Snapshot, script: &[String], mut record: Option<&mut dyn Write>) -> Result<(), Box<dyn std::error::Error>> {
+    let mut session = Session::new(snapshot);
+    let stdin = std::io::stdin();
+    let mut lines = stdin.lock().lines();
+    let mut stdout = std::io::stdout();
+
+    // the screen as it was when the snapshot was taken
+    let mut replay = Bytes::new(std::io::stdout());
+    for &v in &snapshot.output {
+        replay.write(v);
+    }
+    if let Some(e) = replay.error {
+        return Err(e.into());
+    }
+    write!(stdout, "{}", session.play(script)?)?;
+    if let Some(record) = &mut record {
+        for line in script {
+            writeln!(record, "{}", line)?;
+        }
+    }
+
+    while !session.is_over() {
+        let line = match lines.next() {
+            Some(line) => line?,
+            None       => break,
+        };
+        match session.command(&line) {
+            Ok(text)                                                          => write!(stdout, "{}", text)?,
+            // a typo at the keyboard is not worth quitting over
+            Err(e @ (SessionError::NoRoom | SessionError::NoCheckpoint(_))) => { writeln!(stdout, "{}", e)?; continue; },
+            Err(e)                                                            => return Err(e.into()),
+        }
+        if let Some(record) = &mut record {
+            writeln!(record, "{}", line.trim())?;
+            record.flush()?;
+        }
+    }
     Ok(())
 }
+
+//
+// tests
+//
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::util;
+
+    #[test]
+    fn test_25_room() {
+        let text = "\n\n\n== Hull Breach ==\nA hole.\n\nDoors here lead:\n- north\n- west\n\nItems here:\n- mouse\n\nCommand?\n";
+        let room = Room { name: "Hull Breach".into(), doors: vec!["north".into(), "west".into()], items: vec!["mouse".into()] };
+        assert_eq!(Room::parse(text), Some(room));
+        assert_eq!(Room::parse("You can't go that way.\n"), None);
+    }
+
+    #[test]
+    fn test_25a() -> Result<(), Box<dyn std::error::Error>> {
+        let vm = util::get_parsed_line::<VM>("input/day25.txt")?;
+        assert_eq!(day25a(&vm), 537165825);
+        Ok(())
+    }
+
+    #[test]
+    fn test_25_walkthrough() -> Result<(), Box<dyn std::error::Error>> {
+        let vm = util::get_parsed_line::<VM>("input/day25.txt")?;
+        let script = parse_script(&util::get_text("input/day25-walkthrough.txt")?);
+        let transcripts = (0..2)
+            .map(|_| Session::new(&Snapshot::new(&vm, &[], &[])).play(&script))
+            .collect::<Result<Vec<_>, _>>()?;
+        assert!(transcripts[0].contains("typing 537165825 on the keypad"));
+        assert_eq!(transcripts[0], transcripts[1]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_25_save_pending() -> Result<(), Box<dyn std::error::Error>> {
+        // writes "a" and a value, then wants a line
+        let vm = VM::new(&[104, 97, 104, 1000, 3, 100, 99]);
+        let mut session = Session::new(&Snapshot::new(&vm, &[], &[]));
+        assert_eq!(session.game.read()?, Some(AsciiEvent::Line("a".to_owned())));
+        session.game.send_line("x");
+        let snapshot = session.snapshot();
+        assert_eq!((&snapshot.input[..], &snapshot.output[..]), (&[120, 10][..], &[1000][..]));
+        let mut restored = Session::new(&snapshot);
+        assert_eq!(restored.output()?, "1000\n");
+        assert!(restored.is_over());
+        Ok(())
+    }
+
+    #[test]
+    fn test_25_checkpoints() -> Result<(), Box<dyn std::error::Error>> {
+        let vm = util::get_parsed_line::<VM>("input/day25.txt")?;
+        let mut session = Session::new(&Snapshot::new(&vm, &[], &[]));
+        assert!(matches!(session.command("checkpoint"), Err(SessionError::NoRoom)));
+        let start = session.output()?;
+        assert_eq!(session.room.as_deref(), Some("Hull Breach"));
+        session.command("checkpoint")?;
+        session.command("north")?;
+        assert_ne!(session.room.as_deref(), Some("Hull Breach"));
+        assert!(matches!(session.command("restore Nowhere"), Err(SessionError::NoCheckpoint(_))));
+        assert_eq!(session.command("restore Hull Breach")?, "back in Hull Breach\n");
+        assert_eq!(session.room.as_deref(), Some("Hull Breach"));
+        // the same command from the same room gives the same answer
+        let mut fresh = Session::new(&Snapshot::new(&vm, &[], &[]));
+        assert_eq!(fresh.output()?, start);
+        assert_eq!(session.command("inv")?, fresh.command("inv")?);
+        Ok(())
+    }
+}
@@ -1,12 +1,48 @@
-use std::io::Read;
-use std::io::Write;
+use std::convert::TryFrom;
 use std::str::FromStr;
+use std::sync::Arc;
 
-#[derive(Debug, Clone)]
-pub struct VM {
-    pub memory: Vec<i128>,
+pub mod amplifier;
+pub mod ascii;
+pub mod asm;
+pub mod cfg;
+pub mod check;
+pub mod code;
+pub mod debug;
+pub mod decompile;
+pub mod diff;
+pub mod disasm;
+pub mod fuzz;
+pub mod heatmap;
+pub mod io;
+pub mod isa;
+pub mod memory;
+pub mod packet;
+pub mod reverse;
+pub mod runtime;
+pub mod snapshot;
+pub mod symbolic;
+pub mod trace;
+pub mod watchdog;
+pub mod word;
+
+pub use io::{IntcodeInput, IntcodeOutput};
+pub use isa::InstructionSet;
+pub use memory::Memory;
+pub use word::Word;
+
+use code::{Decoded, Op};
+use isa::Action;
+use memory::Cells;
+
+// The word type is the type of a memory cell: i128 unless another one is chosen.
+#[derive(Debug, Clone, PartialEq)]
+pub struct VM<T: Word = i128> {
+    pub memory: Memory<T>,
     pub ip: usize,
     pub bp: i128,
+    // the opcodes this VM understands besides the standard ones
+    pub isa: Arc<InstructionSet>,
 }
 
 #[derive(Debug, Clone, Copy)]
@@ -16,21 +52,157 @@ pub enum Status {
     Suspended
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Mode {
+    Position,
+    Immediate,
+    Relative,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct Opcode {
+    pub code: i128,
+    pub name: &'static str,
+    pub params: usize,
+    pub writes: Option<usize>,
+    pub jumps: Option<usize>,
+}
+
+pub static OPCODES: &[Opcode] = &[
+    Opcode { code: 1,  name: "ADD", params: 3, writes: Some(3), jumps: None },
+    Opcode { code: 2,  name: "MUL", params: 3, writes: Some(3), jumps: None },
+    Opcode { code: 3,  name: "IN",  params: 1, writes: Some(1), jumps: None },
+    Opcode { code: 4,  name: "OUT", params: 1, writes: None,    jumps: None },
+    Opcode { code: 5,  name: "JNZ", params: 2, writes: None,    jumps: Some(2) },
+    Opcode { code: 6,  name: "JZ",  params: 2, writes: None,    jumps: Some(2) },
+    Opcode { code: 7,  name: "LT",  params: 3, writes: Some(3), jumps: None },
+    Opcode { code: 8,  name: "EQ",  params: 3, writes: Some(3), jumps: None },
+    Opcode { code: 9,  name: "BP",  params: 1, writes: None,    jumps: None },
+    Opcode { code: 99, name: "HLT", params: 0, writes: None,    jumps: None },
+];
+
+impl Opcode {
+    pub fn by_code(code: i128) -> Option<&'static Opcode> {
+        OPCODES.iter().find(|op| op.code == code)
+    }
+
+    pub fn by_name(name: &str) -> Option<&'static Opcode> {
+        OPCODES.iter().find(|op| op.name.eq_ignore_ascii_case(name))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Instruction {
+    pub addr: usize,
+    pub op: &'static Opcode,
+    pub modes: Vec<Mode>,
+    pub params: Vec<i128>,
+}
+
+impl Instruction {
+    // decodes the instruction at addr, or None if it is not a valid instruction in the set
+    pub fn decode_with<M: Cells + ?Sized>(isa: &InstructionSet, memory: &M, addr: usize) -> Option<Instruction> {
+        let instr = memory.cell(addr)?;
+        let op = isa.by_code(instr % 100)?;
+        if instr < 0 || instr / 10_i128.pow(op.params as u32 + 2) != 0 {
+            return None;
+        }
+        let mut modes = Vec::with_capacity(op.params);
+        let mut params = Vec::with_capacity(op.params);
+        for i in 1..=op.params {
+            modes.push(match instr / 10_i128.pow(i as u32 + 1) % 10 {
+                0 => Mode::Position,
+                1 if op.writes != Some(i) => Mode::Immediate,
+                2 => Mode::Relative,
+                _ => return None,
+            });
+            params.push(memory.cell(addr + i)?);
+        }
+        Some(Instruction { addr, op, modes, params })
+    }
+
+    pub fn size(&self) -> usize {
+        self.params.len() + 1
+    }
+
+    pub fn end(&self) -> usize {
+        self.addr + self.size()
+    }
+
+    // the address this instruction may jump to, if it is known statically
+    pub fn target(&self) -> Option<i128> {
+        let i = self.op.jumps?;
+        match self.modes[i - 1] {
+            Mode::Immediate => Some(self.params[i - 1]),
+            _               => None,
+        }
+    }
+
+    // whether execution can continue with the next instruction
+    pub fn falls_through(&self) -> bool {
+        match (self.op.code, self.modes.first(), self.params.first()) {
+            (99, _, _)                           => false,
+            (5, Some(Mode::Immediate), Some(&c)) => c == 0,
+            (6, Some(Mode::Immediate), Some(&c)) => c != 0,
+            _                                    => true,
+        }
+    }
+}
+
+impl std::fmt::Display for Instruction {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let operands = self.modes
+            .iter()
+            .zip(&self.params)
+            .map(|(mode, &param)| match mode {
+                Mode::Position              => format!("[{}]", param),
+                Mode::Immediate             => param.to_string(),
+                Mode::Relative if param < 0 => format!("[bp-{}]", -param),
+                Mode::Relative              => format!("[bp+{}]", param),
+            })
+            .collect::<Vec<_>>()
+            .join(", ");
+        write!(f, "{}", format!("{:<3} {}", self.op.name, operands).trim_end())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, thiserror::Error)]
+pub enum VmError {
+    #[error("Unknown opcode {instr} at IP={ip}, BP={bp}")]
+    UnknownOpcode { ip: usize, bp: i128, instr: i128 },
+    #[error("Bad mode {mode} for parameter {param} of instruction {instr} at IP={ip}, BP={bp}")]
+    BadMode { ip: usize, bp: i128, instr: i128, param: usize, mode: i128 },
+    #[error("Negative address {addr} in instruction {instr} at IP={ip}, BP={bp}")]
+    NegativeAddress { ip: usize, bp: i128, instr: i128, addr: i128 },
+    #[error("Write to immediate parameter {param} of instruction {instr} at IP={ip}, BP={bp}")]
+    WriteToImmediate { ip: usize, bp: i128, instr: i128, param: usize },
+    #[error("Program wants to read from empty input at IP={ip}, BP={bp}")]
+    InputStarved { ip: usize, bp: i128, instr: i128 },
+    #[error("Arithmetic overflow in instruction {instr} at IP={ip}, BP={bp}")]
+    Overflow { ip: usize, bp: i128, instr: i128 },
+    #[error("Instruction budget of {budget} used up at IP={ip}, BP={bp}")]
+    BudgetExceeded { ip: usize, bp: i128, instr: i128, budget: u64 },
+    #[error("Deadline passed after {executed} instructions at IP={ip}, BP={bp}")]
+    DeadlinePassed { ip: usize, bp: i128, instr: i128, executed: u64 },
+    #[error("Infinite loop: the machine state repeats every {period} instructions at IP={ip}, BP={bp}")]
+    Cycle { ip: usize, bp: i128, instr: i128, period: u64 },
+}
+
 #[derive(Debug, thiserror::Error)]
 pub enum InputError {
     #[error("Cannot parse number {0:?}")]
-    Parse(#[from] std::num::ParseIntError)
+    Parse(String)
 }
 
-impl FromStr for VM {
+impl<T: Word> FromStr for VM<T> {
     type Err = InputError;
 
     fn from_str(text: &str) -> Result<Self, Self::Err> {
         let memory = text
             .split(',')
-            .map(|s| s.trim().parse::<i128>())
-            .collect::<Result<Vec<i128>, std::num::ParseIntError>>()?;
-        Ok(VM::new(&memory))
+            .map(|s| s.trim().parse::<T>().map_err(|_| InputError::Parse(s.trim().to_owned())))
+            .collect::<Result<Vec<T>, InputError>>()?;
+        Ok(VM::with_program(&memory))
     }
 }
 
@@ -42,115 +214,379 @@ impl AsRef<VM> for VM {
 
 impl VM {
     pub fn new(program: &[i128]) -> VM {
+        VM::with_program(program)
+    }
+}
+
+impl<T: Word> VM<T> {
+    pub fn with_program(program: &[T]) -> Self {
         VM {
-            memory: program.to_vec(),
+            memory: Memory::from(program),
             ip: 0,
-            bp: 0
+            bp: 0,
+            isa: isa::STANDARD.clone(),
         }
     }
 
-    pub fn run_with(&mut self, input: &mut Vec<i128>, output: &mut Vec<i128>) {
+    pub fn with_isa(mut self, isa: Arc<InstructionSet>) -> Self {
+        self.isa = isa;
+        self
+    }
+
+    pub fn run_with<I, O>(&mut self, input: &mut I, output: &mut O) -> Result<(), VmError>
+        where
+            I: IntcodeInput<T> + ?Sized,
+            O: IntcodeOutput<T> + ?Sized
+    {
         loop {
-            match self.step(input, output) {
+            match self.step(input, output)? {
                 Status::Halted      => break,
-                Status::Blocked     => panic!("program wants to read from empty input"),
+                Status::Blocked     => return Err(VmError::InputStarved { ip: self.ip, bp: self.bp, instr: self.instr() }),
                 Status::Suspended   => continue,
             }
         }
+        Ok(())
     }
 
-    pub fn run(&mut self, input: &mut Vec<i128>) -> Vec<i128> {
+    pub fn run<I: IntcodeInput<T> + ?Sized>(&mut self, input: &mut I) -> Result<Vec<T>, VmError> {
         let mut output = vec![];
-        self.run_with(input, &mut output);
-        output
+        self.run_with(input, &mut output)?;
+        Ok(output)
     }
 
-    pub fn step(&mut self, input: &mut Vec<i128>, output: &mut Vec<i128>) -> Status {
-        self.step_impl(|| if input.is_empty() { None } else { Some(input.remove(0)) },
-                       |x| output.push(x))
+    pub fn step<I, O>(&mut self, input: &mut I, output: &mut O) -> Result<Status, VmError>
+        where
+            I: IntcodeInput<T> + ?Sized,
+            O: IntcodeOutput<T> + ?Sized
+    {
+        self.step_impl(|| input.read(), |x| output.write(x))
     }
 
-    pub fn run_stdio(&mut self) {
-        let stdin = std::io::stdin();
-        let stdout = std::io::stdout();
+    pub fn step_impl<R, W>(&mut self, mut read: R, mut write: W) -> Result<Status, VmError>
+        where
+            R: FnMut() -> Option<T>,
+            W: FnMut(T)
+    {
         loop {
-            let status = self.step_impl(|| { let mut buf: [u8; 1] = [0];
-                                             stdin.lock().read_exact(&mut buf).map(|_| buf[0] as i128).ok() },
-                                        |b| { let buf: [u8; 1] = [b as u8];
-                                              stdout.lock().write_all(&buf).unwrap(); });
-            match status {
-                Status::Halted => break,
-                Status::Blocked => break,
-                Status::Suspended => continue,
+            if let Some(status) = self.tick(&mut read, &mut write)? {
+                return Ok(status);
             }
         }
     }
 
-    pub fn step_impl<R, W>(&mut self, mut read: R, mut write: W) -> Status
+    // executes a single instruction; returns a status if execution should not simply continue
+    pub fn tick<R, W>(&mut self, read: &mut R, write: &mut W) -> Result<Option<Status>, VmError>
         where
-            R: FnMut() -> Option<i128>,
-            W: FnMut(i128)
+            R: FnMut() -> Option<T>,
+            W: FnMut(T)
     {
-        loop {
-            match self.memory[self.ip] % 100 {
-                // day 2 : add
-                1   => { *self.arg(3) = *self.arg(1) + *self.arg(2);
-                         self.ip += 4; },
-
-                // day 2 : mul
-                2   => { *self.arg(3) = *self.arg(1) * *self.arg(2);
-                         self.ip += 4; },
-
-                // day 5 : in
-                3   => { match read() {
-                             Some(x) => { *self.arg(1) = x;
-                                          self.ip += 2 },
-                             None    => return Status::Blocked,
-                         }
-                       },
-
-                // day 5 : out
-                4   => { write(*self.arg(1));
-                         self.ip += 2;
-                         return Status::Suspended; }
-
-                // day 5 : jnz
-                5   => { self.ip = if *self.arg(1) != 0 { *self.arg(2) as usize } else { self.ip + 3 } },
-
-                // day 5 : jz
-                6   => { self.ip = if *self.arg(1) == 0 { *self.arg(2) as usize } else { self.ip + 3 } },
-
-                // day 5 : lt
-                7   => { *self.arg(3) = if *self.arg(1) < *self.arg(2) { 1 } else { 0 };
-                         self.ip += 4; },
-
-                // day 5 : eq
-                8   => { *self.arg(3) = if *self.arg(1) == *self.arg(2) { 1 } else { 0 };
-                         self.ip += 4 },
-
-                // day 9 : add bp
-                9   => { self.bp += *self.arg(1);
-                         self.ip += 2; },
-
-                // day 2 : halt
-                99  => return Status::Halted,
-
-                // day 2 : wtf
-                _   => panic!("unrecognized opcode {}; IP={}", self.memory[self.ip], self.ip)
-            };
+        let d = self.memory.decoded(self.ip);
+        match d.op {
+            // day 2 : add
+            Op::Add     => { let x = self.arg(d, 1)?.checked_add(&self.arg(d, 2)?).ok_or_else(|| self.overflow())?;
+                             *self.dst(d, 3)? = x;
+                             self.ip += 4; },
+
+            // day 2 : mul
+            Op::Mul     => { let x = self.arg(d, 1)?.checked_mul(&self.arg(d, 2)?).ok_or_else(|| self.overflow())?;
+                             *self.dst(d, 3)? = x;
+                             self.ip += 4; },
+
+            // day 5 : in
+            Op::In      => { let dst = self.dst(d, 1)?;
+                             match read() {
+                                 Some(x) => { *dst = x;
+                                              self.ip += 2 },
+                                 None    => return Ok(Some(Status::Blocked)),
+                             }
+                           },
+
+            // day 5 : out
+            Op::Out     => { write(self.arg(d, 1)?);
+                             self.ip += 2;
+                             return Ok(Some(Status::Suspended)); }
+
+            // day 5 : jnz
+            Op::Jnz     => { self.ip = if !self.arg(d, 1)?.is_zero() { let t = self.arg(d, 2)?; self.jump(t)? } else { self.ip + 3 } },
+
+            // day 5 : jz
+            Op::Jz      => { self.ip = if self.arg(d, 1)?.is_zero() { let t = self.arg(d, 2)?; self.jump(t)? } else { self.ip + 3 } },
+
+            // day 5 : lt
+            Op::Lt      => { let x = T::from_bool(self.arg(d, 1)? < self.arg(d, 2)?);
+                             *self.dst(d, 3)? = x;
+                             self.ip += 4; },
+
+            // day 5 : eq
+            Op::Eq      => { let x = T::from_bool(self.arg(d, 1)? == self.arg(d, 2)?);
+                             *self.dst(d, 3)? = x;
+                             self.ip += 4 },
+
+            // day 9 : add bp
+            Op::Bp      => { self.bp = self.arg(d, 1)?.to_i128().and_then(|x| self.bp.checked_add(x)).ok_or_else(|| self.overflow())?;
+                             self.ip += 2; },
+
+            // day 2 : halt
+            Op::Halt    => return Ok(Some(Status::Halted)),
+
+            // day 2 : wtf, unless the instruction set knows better
+            Op::Unknown => return self.custom(d, write),
+        };
+        Ok(None)
+    }
+
+    // runs an opcode added by the instruction set; the parameter it writes is passed as an address
+    fn custom<W: FnMut(T)>(&mut self, d: Decoded, write: &mut W) -> Result<Option<Status>, VmError> {
+        let (op, handler) = self.isa
+            .handler(self.instr() % 100)
+            .ok_or_else(|| VmError::UnknownOpcode { ip: self.ip, bp: self.bp, instr: self.instr() })?;
+        let args = (1..=op.params)
+            .map(|i| match op.writes {
+                Some(w) if w == i => self.addr(d, i).map(|addr| addr as i128),
+                _                 => self.arg(d, i)?.to_i128().ok_or_else(|| self.overflow()),
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        let word = |vm: &Self, v: i128| T::from_i128(v).ok_or_else(|| vm.overflow());
+        match handler(&args) {
+            Action::Next     => (),
+            Action::Store(v) => if let Some(i) = op.writes {
+                                    let v = word(self, v)?;
+                                    *self.dst(d, i)? = v;
+                                },
+            Action::Jump(a)  => { self.ip = self.jump(word(self, a)?)?;
+                                  return Ok(None); },
+            Action::Exit(v)  => { write(word(self, v)?);
+                                  return Ok(Some(Status::Halted)); },
+        }
+        self.ip += op.params + 1;
+        Ok(None)
+    }
+
+    fn load(&self, addr: usize) -> T {
+        self.memory.load(addr)
+    }
+
+    // the instruction tick is about to run, decoded the way tick decodes it, so digits above the
+    // modes do not matter; None if it fails before doing anything
+    fn executing(&mut self) -> Option<Instruction> {
+        let d = self.memory.decoded(self.ip);
+        let op = self.isa.by_code(self.instr() % 100)?;
+        let mut modes = Vec::with_capacity(op.params);
+        let mut params = Vec::with_capacity(op.params);
+        for i in 1..=op.params {
+            modes.push(match d.modes[i - 1] {
+                0 => Mode::Position,
+                1 if op.writes != Some(i) => Mode::Immediate,
+                2 => Mode::Relative,
+                _ => return None,
+            });
+            params.push(self.load(self.ip + i).to_i128()?);
         }
+        Some(Instruction { addr: self.ip, op, modes, params })
+    }
+
+    // the current instruction; one too big for an i128 is not a valid instruction anyway
+    fn instr(&self) -> i128 {
+        self.load(self.ip).to_i128().unwrap_or(i128::MAX)
+    }
+
+    fn overflow(&self) -> VmError {
+        VmError::Overflow { ip: self.ip, bp: self.bp, instr: self.instr() }
     }
 
-    fn arg(&mut self, i: usize) -> &mut i128 {
-        let o = match self.memory[self.ip] / 10_i128.pow((i as u32) + 1) % 10 {
-            0 => self.memory[self.ip + i] as usize,
-            1 => self.ip + i,
-            2 => (self.memory[self.ip + i] + self.bp) as usize,
-            _ => panic!("bad opcode {} at IP {}", self.memory[self.ip] as usize, self.ip)
+    fn jump(&self, target: T) -> Result<usize, VmError> {
+        let target = target.to_i128().ok_or_else(|| self.overflow())?;
+        usize::try_from(target).map_err(|_| VmError::NegativeAddress { ip: self.ip, bp: self.bp, instr: self.instr(), addr: target })
+    }
+
+    fn addr(&self, d: Decoded, i: usize) -> Result<usize, VmError> {
+        let addr = match d.modes[i - 1] {
+            0 => self.load(self.ip + i).to_i128(),
+            1 => return Ok(self.ip + i),
+            2 => self.load(self.ip + i).to_i128().and_then(|x| x.checked_add(self.bp)),
+            m => return Err(VmError::BadMode { ip: self.ip, bp: self.bp, instr: self.instr(), param: i, mode: m as i128 })
         };
-        if o >= self.memory.len() {
-            self.memory.resize(o+1, 0);
+        let addr = addr.ok_or_else(|| self.overflow())?;
+        usize::try_from(addr).map_err(|_| VmError::NegativeAddress { ip: self.ip, bp: self.bp, instr: self.instr(), addr })
+    }
+
+    fn cell(&mut self, o: usize) -> &mut T {
+        self.memory.cell_mut(o)
+    }
+
+    fn arg(&self, d: Decoded, i: usize) -> Result<T, VmError> {
+        let o = self.addr(d, i)?;
+        Ok(self.load(o))
+    }
+
+    fn dst(&mut self, d: Decoded, i: usize) -> Result<&mut T, VmError> {
+        if d.modes[i - 1] == 1 {
+            return Err(VmError::WriteToImmediate { ip: self.ip, bp: self.bp, instr: self.instr(), param: i });
         }
-        &mut self.memory[o]
+        let o = self.addr(d, i)?;
+        Ok(self.cell(o))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use num_bigint::BigInt;
+    use crate::util;
+
+    #[test]
+    fn test_error_unknown_opcode() {
+        let mut vm = VM::new(&[1101, 1, 1, 5, 42, 0]);
+        let err = vm.run(&mut &[][..]).unwrap_err();
+        assert_eq!(err, VmError::UnknownOpcode { ip: 4, bp: 0, instr: 42 });
+    }
+
+    #[test]
+    fn test_error_bad_mode() {
+        let mut vm = VM::new(&[301, 0, 0, 0, 99]);
+        let err = vm.run(&mut &[][..]).unwrap_err();
+        assert_eq!(err, VmError::BadMode { ip: 0, bp: 0, instr: 301, param: 1, mode: 3 });
+    }
+
+    #[test]
+    fn test_error_negative_address() {
+        let mut vm = VM::new(&[109, -10, 204, 2, 99]);
+        let err = vm.run(&mut &[][..]).unwrap_err();
+        assert_eq!(err, VmError::NegativeAddress { ip: 2, bp: -10, instr: 204, addr: -8 });
+    }
+
+    #[test]
+    fn test_error_negative_jump() {
+        let mut vm = VM::new(&[1105, 1, -1]);
+        let err = vm.run(&mut &[][..]).unwrap_err();
+        assert_eq!(err, VmError::NegativeAddress { ip: 0, bp: 0, instr: 1105, addr: -1 });
+    }
+
+    #[test]
+    fn test_error_write_to_immediate() {
+        let mut vm = VM::new(&[11101, 1, 1, 0, 99]);
+        let err = vm.run(&mut &[][..]).unwrap_err();
+        assert_eq!(err, VmError::WriteToImmediate { ip: 0, bp: 0, instr: 11101, param: 3 });
+    }
+
+    #[test]
+    fn test_error_input_starved() {
+        let mut vm = VM::new(&[3, 0, 3, 0, 99]);
+        let err = vm.run(&mut &[1][..]).unwrap_err();
+        assert_eq!(err, VmError::InputStarved { ip: 2, bp: 0, instr: 3 });
+    }
+
+    #[test]
+    fn test_self_modifying() {
+        // outputs [20], then turns that instruction into an immediate output of 20 and loops once
+        let mut vm = VM::new(&[4, 20, 1101, 0, 104, 0, 1001, 21, 1, 21, 1008, 21, 2, 22, 1006, 22, 0, 99, 0, 0, 7, 0, 0]);
+        assert_eq!(vm.run(&mut &[][..]).unwrap(), vec![7, 20]);
+    }
+
+    #[test]
+    fn test_error_overflow() {
+        let mut vm = VM::<i64>::with_program(&[1102, 1 << 32, 1 << 32, 0, 99]);
+        assert_eq!(vm.run(&mut &[][..]).unwrap_err(), VmError::Overflow { ip: 0, bp: 0, instr: 1102 });
+        let mut vm = VM::new(&[1101, i128::MAX, 1, 0, 99]);
+        assert_eq!(vm.run(&mut &[][..]).unwrap_err(), VmError::Overflow { ip: 0, bp: 0, instr: 1101 });
+        let mut vm = VM::<BigInt>::with_program(&[BigInt::from(1101), BigInt::from(i128::MAX), BigInt::from(1), BigInt::from(0), BigInt::from(99)]);
+        vm.run(&mut &[][..]).unwrap();
+        assert_eq!(vm.memory[0].to_string(), "170141183460469231731687303715884105728");
+    }
+
+    #[test]
+    fn test_word_bignum() -> Result<(), Box<dyn std::error::Error>> {
+        // doubles its input a hundred times
+        let mut vm = "3,20,1101,0,100,21,1002,20,2,20,1001,21,-1,21,1005,21,6,4,20,99,0,0".parse::<VM<BigInt>>()?;
+        let output = vm.run(&mut &[BigInt::from(1)][..])?;
+        assert_eq!(output[0].to_string(), "1267650600228229401496703205376");
+        Ok(())
+    }
+
+    // runs a program with the given word type until it halts or wants more input, converting input
+    // and output from and to i128
+    fn run_as<T: Word>(vm: &VM, input: &[i128]) -> Result<Vec<i128>, VmError> {
+        let program = vm.memory.iter().map(|v| T::from_i128(v).unwrap()).collect::<Vec<_>>();
+        let input = input.iter().map(|&v| T::from_i128(v).unwrap()).collect::<Vec<_>>();
+        let mut output = vec![];
+        match VM::with_program(&program).run_with(&mut &input[..], &mut output) {
+            Ok(()) | Err(VmError::InputStarved { .. }) => (),
+            Err(e)                                     => return Err(e),
+        }
+        Ok(output.iter().map(|v| v.to_i128().unwrap()).collect())
+    }
+
+    fn run_day02<T: Word>(vm: &VM) -> Result<i128, VmError> {
+        let mut program = vm.memory.iter().map(|v| T::from_i128(v).unwrap()).collect::<Vec<_>>();
+        program[1] = T::from_i128(12).unwrap();
+        program[2] = T::from_i128(2).unwrap();
+        let mut vm = VM::with_program(&program);
+        vm.run(&mut &[][..])?;
+        Ok(vm.memory[0].to_i128().unwrap())
+    }
+
+    fn assert_all_words(vm: &VM, input: &[i128]) -> Result<(), Box<dyn std::error::Error>> {
+        let expected = run_as::<i128>(vm, input)?;
+        assert!(!expected.is_empty());
+        assert_eq!(run_as::<i64>(vm, input)?, expected);
+        assert_eq!(run_as::<i128>(vm, input)?, expected);
+        assert_eq!(run_as::<BigInt>(vm, input)?, expected);
+        Ok(())
+    }
+
+    #[test]
+    fn test_word_puzzles() -> Result<(), Box<dyn std::error::Error>> {
+        // day 2 leaves its answer in memory rather than outputting it
+        let day02 = util::get_parsed_line::<VM>("input/day02.txt")?;
+        assert_eq!(run_day02::<i64>(&day02)?, 4930687);
+        assert_eq!(run_day02::<i128>(&day02)?, 4930687);
+        assert_eq!(run_day02::<BigInt>(&day02)?, 4930687);
+
+        let day05 = util::get_parsed_line::<VM>("input/day05.txt")?;
+        assert_all_words(&day05, &[1])?;
+        assert_all_words(&day05, &[5])?;
+
+        let day09 = util::get_parsed_line::<VM>("input/day09.txt")?;
+        assert_all_words(&day09, &[1])?;
+        assert_all_words(&day09, &[2])?;
+
+        let day13 = util::get_parsed_line::<VM>("input/day13.txt")?;
+        assert_all_words(&day13, &[])?;
+
+        let day19 = util::get_parsed_line::<VM>("input/day19.txt")?;
+        for &(x, y) in &[(0, 0), (10, 10), (30, 40), (1000, 1200)] {
+            assert_all_words(&day19, &[x, y])?;
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_word_interactive_puzzles() -> Result<(), Box<dyn std::error::Error>> {
+        // the interactive days are compared on the start of a session, up to where they want more input
+        let ascii = |text: &str| text.bytes().map(i128::from).collect::<Vec<_>>();
+
+        let day07 = util::get_parsed_line::<VM>("input/day07.txt")?;
+        assert_all_words(&day07, &[3, 0])?;
+        assert_all_words(&day07, &[7, 0, 12])?;
+
+        let day11 = util::get_parsed_line::<VM>("input/day11.txt")?;
+        assert_all_words(&day11, &[1, 0, 0, 1])?;
+
+        let day15 = util::get_parsed_line::<VM>("input/day15.txt")?;
+        assert_all_words(&day15, &[1, 2, 3, 4, 4, 4])?;
+
+        let day17 = util::get_parsed_line::<VM>("input/day17.txt")?;
+        assert_all_words(&day17, &[])?;
+
+        let day21 = util::get_parsed_line::<VM>("input/day21.txt")?;
+        assert_all_words(&day21, &ascii("NOT J T\nAND A T\nAND B T\nAND C T\nNOT T J\nAND D J\nWALK\n"))?;
+
+        let day23 = util::get_parsed_line::<VM>("input/day23.txt")?;
+        assert_all_words(&day23, &[0, -1, -1])?;
+
+        let day25 = util::get_parsed_line::<VM>("input/day25.txt")?;
+        assert_all_words(&day25, &ascii("inv\nnorth\n"))?;
+        Ok(())
     }
 }
@@ -0,0 +1,142 @@
+use crate::intcode::{VM, VmError};
+use crate::intcode::runtime::{Event, Lockstep, Topology};
+
+//
+// struct AmplifierChain -- copies of a program, each feeding its output to the next
+//
+
+// One amplifier per phase setting, each running the same program. Every amplifier gets its phase
+// first, then the signal from the one before it; with feedback, the first one also gets the
+// signals from the last one. The amplifiers are a pipeline or a ring on a Lockstep network, so they
+// take turns on the calling thread.
+pub struct AmplifierChain<'a> {
+    vm: &'a VM,
+    phases: Vec<i128>,
+    feedback: bool,
+}
+
+impl<'a> AmplifierChain<'a> {
+    pub fn new(vm: &'a VM, phases: &[i128]) -> Self {
+        AmplifierChain { vm, phases: phases.to_vec(), feedback: false }
+    }
+
+    pub fn feedback(self) -> Self {
+        AmplifierChain { feedback: true, ..self }
+    }
+
+    // the last signal the last amplifier sends, if it sends any; amplifiers that all wait for
+    // input fail with the error of the first one
+    pub fn run(&self, signal: i128) -> Result<Option<i128>, VmError> {
+        // the first signal is queued up front, or the amplifiers would deadlock before it is sent
+        let inputs = self.phases.iter().enumerate().map(|(i, &phase)| if i == 0 { vec![phase, signal] } else { vec![phase] });
+        let topology = if self.feedback { Topology::Ring } else { Topology::Pipeline };
+        let mut network = Lockstep::new(topology, inputs.map(|input| (self.vm.clone(), input)).collect());
+        let mut last = None;
+        while let Some(event) = network.next_event() {
+            match event {
+                Event::Output(_, values) => last = values.last().copied().or(last),
+                Event::Error(_, e)       => return Err(e),
+                Event::Deadlock          => { let vm = (0..self.phases.len()).find(|&i| !network.is_halted(i)).map(|i| network.vm(i)).unwrap();
+                                              return Err(VmError::InputStarved { ip: vm.ip, bp: vm.bp, instr: vm.instr() }); },
+                _                        => (),
+            }
+        }
+        Ok(last)
+    }
+
+    // tries every ordering of the phases, spread over all cores, and returns the one that gives
+    // the strongest signal
+    pub fn search(&self, signal: i128) -> Result<Option<(Vec<i128>, i128)>, VmError> {
+        self.search_on(std::thread::available_parallelism().map_or(1, |n| n.get()), signal)
+    }
+
+    // Each thread tries a chunk of the orderings in turn. The chunks are combined in order, so the
+    // result, and the error if any, is the same as trying them all on one thread.
+    fn search_on(&self, threads: usize, signal: i128) -> Result<Option<(Vec<i128>, i128)>, VmError> {
+        let mut phases = self.phases.clone();
+        let orderings = permutohedron::Heap::new(&mut phases).collect::<Vec<_>>();
+        let chunk = orderings.len().div_ceil(threads.max(1)).max(1);
+        std::thread::scope(|scope| {
+            let workers = orderings
+                .chunks(chunk)
+                .map(|chunk| scope.spawn(move || self.best(chunk, signal)))
+                .collect::<Vec<_>>();
+            let mut best = None;
+            for worker in workers {
+                best = AmplifierChain::better(best, worker.join().unwrap()?);
+            }
+            Ok(best)
+        })
+    }
+
+    fn best(&self, orderings: &[Vec<i128>], signal: i128) -> Result<Option<(Vec<i128>, i128)>, VmError> {
+        let mut best = None;
+        for ordering in orderings {
+            let chain = AmplifierChain { vm: self.vm, phases: ordering.clone(), feedback: self.feedback };
+            best = AmplifierChain::better(best, chain.run(signal)?.map(|output| (chain.phases, output)));
+        }
+        Ok(best)
+    }
+
+    // the first of the two on a tie
+    fn better(a: Option<(Vec<i128>, i128)>, b: Option<(Vec<i128>, i128)>) -> Option<(Vec<i128>, i128)> {
+        match (a, b) {
+            (Some(a), Some(b)) => Some(if b.1 > a.1 { b } else { a }),
+            (a, b)             => a.or(b),
+        }
+    }
+}
+
+//
+// tests
+//
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::util;
+
+    #[test]
+    fn test_amplifier_chain() -> Result<(), Box<dyn std::error::Error>> {
+        let vm = "3,15,3,16,1002,16,10,16,1,16,15,15,4,15,99,0,0".parse()?;
+        assert_eq!(AmplifierChain::new(&vm, &[0, 1, 2, 3, 4]).search(0)?, Some((vec![4, 3, 2, 1, 0], 43210)));
+        assert_eq!(AmplifierChain::new(&vm, &[4, 3, 2, 1, 0]).run(0)?, Some(43210));
+        assert_eq!(AmplifierChain::new(&vm, &[1, 2]).run(5)?, Some(512));
+        let vm = "3,26,1001,26,-4,26,3,27,1002,27,2,27,1,27,26,27,4,27,1001,28,-1,28,1005,28,6,99,0,0,5".parse()?;
+        assert_eq!(AmplifierChain::new(&vm, &[5, 6, 7, 8, 9]).feedback().search(0)?, Some((vec![9, 8, 7, 6, 5], 139629729)));
+        Ok(())
+    }
+
+    #[test]
+    fn test_amplifier_parallel() -> Result<(), Box<dyn std::error::Error>> {
+        // any number of threads finds what trying every ordering on one thread finds
+        let vm = util::get_parsed_line::<VM>("input/day07.txt")?;
+        for chain in &[AmplifierChain::new(&vm, &[0, 1, 2, 3, 4]), AmplifierChain::new(&vm, &[5, 6, 7, 8, 9]).feedback()] {
+            let mut phases = chain.phases.clone();
+            let sequential = chain.best(&permutohedron::Heap::new(&mut phases).collect::<Vec<_>>(), 0)?;
+            assert!(sequential.is_some());
+            for &threads in &[1, 3, 4, 200] {
+                assert_eq!(chain.search_on(threads, 0)?, sequential);
+            }
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_amplifier_empty() -> Result<(), VmError> {
+        let vm = VM::new(&[3, 0, 4, 0, 99]);
+        assert_eq!(AmplifierChain::new(&vm, &[]).run(0)?, None);
+        assert_eq!(AmplifierChain::new(&vm, &[]).feedback().search(0)?, None);
+        Ok(())
+    }
+
+    #[test]
+    fn test_amplifier_errors() {
+        // wants a third number that never comes
+        let vm = VM::new(&[3, 0, 3, 0, 3, 0, 99]);
+        assert_eq!(AmplifierChain::new(&vm, &[1, 2]).run(0), Err(VmError::InputStarved { ip: 4, bp: 0, instr: 3 }));
+        let vm = VM::new(&[3, 0, 42]);
+        assert_eq!(AmplifierChain::new(&vm, &[1]).search(0), Err(VmError::UnknownOpcode { ip: 2, bp: 0, instr: 42 }));
+    }
+}
@@ -0,0 +1,208 @@
+use std::collections::VecDeque;
+use std::fmt;
+use std::mem;
+
+use regex::Regex;
+
+use crate::intcode::{Status, VM, VmError};
+
+//
+// enum AsciiEvent
+//
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AsciiEvent {
+    // a line of text without its newline, or the start of one when the program stops for input
+    Line(String),
+    // an output that is not ASCII, usually the answer
+    Value(i128),
+}
+
+impl fmt::Display for AsciiEvent {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            AsciiEvent::Line(line)  => write!(f, "{}", line),
+            AsciiEvent::Value(v)    => write!(f, "{}", v),
+        }
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum AsciiError {
+    #[error(transparent)]
+    Vm(#[from] VmError),
+    #[error("Bad pattern: {0}")]
+    Pattern(#[from] regex::Error),
+    #[error("Program stopped before printing {pattern:?}, after:\n{transcript}")]
+    NoMatch { pattern: String, transcript: String },
+}
+
+//
+// struct AsciiVm -- a VM that reads and writes lines of text
+//
+
+#[derive(Clone)]
+pub struct AsciiVm {
+    pub vm: VM,
+    // values not read by the program yet
+    pub input: VecDeque<i128>,
+    // output since the last newline
+    line: String,
+    events: VecDeque<AsciiEvent>,
+    halted: bool,
+}
+
+impl AsciiVm {
+    pub fn new(vm: VM) -> Self {
+        AsciiVm { vm, input: VecDeque::new(), line: String::new(), events: VecDeque::new(), halted: false }
+    }
+
+    pub fn send_line(&mut self, line: &str) {
+        self.input.extend(line.bytes().chain(Some(b'\n')).map(i128::from));
+    }
+
+    pub fn is_halted(&self) -> bool {
+        self.halted
+    }
+
+    // runs until the next event; None once the program has halted or waits for input
+    pub fn read(&mut self) -> Result<Option<AsciiEvent>, VmError> {
+        let mut output = vec![];
+        while self.events.is_empty() {
+            if self.halted {
+                return Ok(self.flush());
+            }
+            match self.vm.step(&mut self.input, &mut output)? {
+                Status::Suspended => (),
+                Status::Blocked   => return Ok(self.flush()),
+                Status::Halted    => { self.halted = true; continue; },
+            }
+            if let Some(v) = output.pop() {
+                self.take(v);
+            }
+        }
+        Ok(self.events.pop_front())
+    }
+
+    // output the program wrote that was not read yet, as the values it wrote
+    pub fn unread(&self) -> Vec<i128> {
+        let mut values = vec![];
+        for event in &self.events {
+            match event {
+                AsciiEvent::Line(line) => values.extend(line.bytes().chain(Some(b'\n')).map(i128::from)),
+                AsciiEvent::Value(v)   => values.push(*v),
+            }
+        }
+        values.extend(self.line.bytes().map(i128::from));
+        values
+    }
+
+    // puts back output that was not read, as if the program had just written it
+    pub fn put_back(&mut self, values: &[i128]) {
+        for &v in values {
+            self.take(v);
+        }
+    }
+
+    // all events until the program halts or waits for input
+    pub fn read_all(&mut self) -> Result<Vec<AsciiEvent>, VmError> {
+        let mut events = vec![];
+        while let Some(event) = self.read()? {
+            events.push(event);
+        }
+        Ok(events)
+    }
+
+    // reads until a line matches the pattern, and returns the events up to and including it
+    pub fn expect(&mut self, pattern: &str) -> Result<Vec<AsciiEvent>, AsciiError> {
+        let re = Regex::new(pattern)?;
+        let mut events = vec![];
+        while let Some(event) = self.read()? {
+            let found = matches!(&event, AsciiEvent::Line(line) if re.is_match(line));
+            events.push(event);
+            if found {
+                return Ok(events);
+            }
+        }
+        Err(AsciiError::NoMatch { pattern: pattern.to_owned(), transcript: transcript(&events) })
+    }
+
+    // waits for the prompt, then answers it
+    pub fn respond(&mut self, pattern: &str, line: &str) -> Result<Vec<AsciiEvent>, AsciiError> {
+        let events = self.expect(pattern)?;
+        self.send_line(line);
+        Ok(events)
+    }
+
+    fn take(&mut self, v: i128) {
+        match v {
+            10          => { let line = mem::take(&mut self.line);
+                             self.events.push_back(AsciiEvent::Line(line)); },
+            c @ 0..=127 => self.line.push(c as u8 as char),
+            v           => { let line = self.flush();
+                             self.events.extend(line);
+                             self.events.push_back(AsciiEvent::Value(v)); },
+        }
+    }
+
+    fn flush(&mut self) -> Option<AsciiEvent> {
+        match self.line.is_empty() {
+            true  => None,
+            false => Some(AsciiEvent::Line(mem::take(&mut self.line))),
+        }
+    }
+}
+
+// the text of the events, one per line
+pub fn transcript(events: &[AsciiEvent]) -> String {
+    events.iter().map(|event| format!("{}\n", event)).collect()
+}
+
+//
+// tests
+//
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::intcode::asm;
+
+    fn greeter() -> VM {
+        asm::assemble(r#"
+                OUT  72
+                OUT  105
+                OUT  10
+                OUT  62         ; a prompt without a newline
+                IN   [c]
+                ADD  [c], 1000, [c]
+                OUT  [c]
+                HLT
+            c:  DW   0
+        "#).unwrap()
+    }
+
+    #[test]
+    fn test_ascii_events() -> Result<(), VmError> {
+        let mut ascii = AsciiVm::new(greeter());
+        assert_eq!(ascii.read_all()?, vec![AsciiEvent::Line("Hi".into()), AsciiEvent::Line(">".into())]);
+        assert!(!ascii.is_halted());
+        ascii.send_line("A");
+        assert_eq!(ascii.read_all()?, vec![AsciiEvent::Value(1065)]);
+        assert!(ascii.is_halted());
+        assert_eq!(ascii.read()?, None);
+        Ok(())
+    }
+
+    #[test]
+    fn test_ascii_expect() -> Result<(), AsciiError> {
+        let mut ascii = AsciiVm::new(greeter());
+        assert_eq!(transcript(&ascii.respond("^>$", "B")?), "Hi\n>\n");
+        match ascii.expect("nothing") {
+            Err(AsciiError::NoMatch { transcript, .. }) => assert_eq!(transcript, "1066\n"),
+            wtf                                         => panic!("{:?}", wtf),
+        }
+        assert!(matches!(AsciiVm::new(greeter()).expect("("), Err(AsciiError::Pattern(_))));
+        Ok(())
+    }
+}
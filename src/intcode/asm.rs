@@ -0,0 +1,382 @@
+use std::collections::HashMap;
+
+use std::sync::Arc;
+
+use crate::intcode::{InstructionSet, Mode, Opcode, VM};
+use crate::intcode::isa;
+
+//
+// enum AsmError
+//
+
+#[derive(Debug, Clone, PartialEq, thiserror::Error)]
+pub enum AsmError {
+    #[error("Line {0}: unknown mnemonic {1:?}")]
+    UnknownMnemonic(usize, String),
+    #[error("Line {0}: {1} expects {2} operands, got {3}")]
+    OperandCount(usize, String, usize, usize),
+    #[error("Line {0}: cannot parse operand {1:?}")]
+    BadOperand(usize, String),
+    #[error("Line {0}: operand {1} of {2} is written to and cannot be immediate")]
+    ImmediateDestination(usize, usize, String),
+    #[error("Line {0}: bad label {1:?}")]
+    BadLabel(usize, String),
+    #[error("Line {0}: label {1:?} defined more than once")]
+    DuplicateLabel(usize, String),
+    #[error("Line {0}: undefined label {1:?}")]
+    UndefinedLabel(usize, String),
+    #[error("Line {0}: unterminated string")]
+    UnterminatedString(usize),
+}
+
+//
+// enum Expr -- a number, a label or a label with an offset
+//
+
+#[derive(Debug, Clone, PartialEq)]
+enum Expr {
+    Number(i128),
+    Label(String, i128),
+}
+
+impl Expr {
+    fn parse(line: usize, text: &str) -> Result<Expr, AsmError> {
+        let text = text.trim();
+        if let Ok(n) = text.parse::<i128>() {
+            return Ok(Expr::Number(n));
+        }
+        let (name, offset) = match text.find(['+', '-']) {
+            Some(i) => (text[..i].trim(), text[i..].replace(' ', "").trim_start_matches('+').parse::<i128>()
+                                                    .map_err(|_| AsmError::BadOperand(line, text.to_owned()))?),
+            None    => (text, 0),
+        };
+        if is_identifier(name) {
+            Ok(Expr::Label(name.to_owned(), offset))
+        }
+        else {
+            Err(AsmError::BadOperand(line, text.to_owned()))
+        }
+    }
+
+    fn eval(&self, line: usize, labels: &HashMap<String, usize>) -> Result<i128, AsmError> {
+        match self {
+            Expr::Number(n)             => Ok(*n),
+            Expr::Label(name, offset)   => labels
+                .get(name)
+                .map(|&addr| addr as i128 + offset)
+                .ok_or_else(|| AsmError::UndefinedLabel(line, name.clone())),
+        }
+    }
+}
+
+fn is_identifier(text: &str) -> bool {
+    let mut chars = text.chars();
+    matches!(chars.next(), Some(c) if c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+        && !text.eq_ignore_ascii_case("bp")
+}
+
+//
+// struct Operand
+//
+
+#[derive(Debug, Clone, PartialEq)]
+struct Operand {
+    mode: Mode,
+    expr: Expr,
+}
+
+impl Operand {
+    fn parse(line: usize, text: &str) -> Result<Operand, AsmError> {
+        let text = text.trim();
+        if !(text.starts_with('[') && text.ends_with(']')) {
+            return Ok(Operand { mode: Mode::Immediate, expr: Expr::parse(line, text)? });
+        }
+        let inner = text[1..text.len()-1].trim();
+        let lower = inner.to_ascii_lowercase();
+        if lower == "bp" {
+            Ok(Operand { mode: Mode::Relative, expr: Expr::Number(0) })
+        }
+        else if lower.starts_with("bp") && inner[2..].trim_start().starts_with(['+', '-']) {
+            let offset = inner[2..].replace(' ', "");
+            let offset = offset.trim_start_matches('+').parse::<i128>()
+                .map_err(|_| AsmError::BadOperand(line, text.to_owned()))?;
+            Ok(Operand { mode: Mode::Relative, expr: Expr::Number(offset) })
+        }
+        else {
+            Ok(Operand { mode: Mode::Position, expr: Expr::parse(line, inner)? })
+        }
+    }
+}
+
+//
+// enum Statement
+//
+
+#[derive(Debug)]
+enum Statement {
+    Instruction(&'static Opcode, Vec<Operand>),
+    Data(Vec<Expr>),
+}
+
+impl Statement {
+    fn size(&self) -> usize {
+        match self {
+            Statement::Instruction(_, operands) => operands.len() + 1,
+            Statement::Data(values)             => values.len(),
+        }
+    }
+}
+
+//
+// parsing
+//
+
+// splits on commas that are not inside a string literal
+fn split_operands(line: usize, text: &str) -> Result<Vec<String>, AsmError> {
+    let mut result = vec![];
+    let mut current = String::new();
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            ',' => result.push(std::mem::take(&mut current)),
+            '"' => {
+                current.push(c);
+                loop {
+                    match chars.next() {
+                        Some('\\') => { current.push('\\');
+                                        current.push(chars.next().ok_or(AsmError::UnterminatedString(line))?); },
+                        Some('"')  => { current.push('"'); break },
+                        Some(c)    => current.push(c),
+                        None       => return Err(AsmError::UnterminatedString(line)),
+                    }
+                }
+            },
+            _   => current.push(c),
+        }
+    }
+    if !current.trim().is_empty() || !result.is_empty() {
+        result.push(current);
+    }
+    Ok(result.iter().map(|s| s.trim().to_owned()).collect())
+}
+
+fn parse_string(line: usize, text: &str) -> Result<Vec<Expr>, AsmError> {
+    let mut result = vec![];
+    let mut chars = text[1..text.len()-1].chars();
+    while let Some(c) = chars.next() {
+        let c = match c {
+            '\\' => match chars.next() {
+                Some('n')  => '\n',
+                Some('t')  => '\t',
+                Some('0')  => '\0',
+                Some(c)    => c,
+                None       => return Err(AsmError::UnterminatedString(line)),
+            },
+            c    => c,
+        };
+        result.push(Expr::Number(c as i128));
+    }
+    Ok(result)
+}
+
+// strips a comment, taking care not to cut a string literal containing ';'
+fn strip_comment(text: &str) -> &str {
+    let mut in_string = false;
+    let mut escaped = false;
+    for (i, c) in text.char_indices() {
+        match c {
+            _ if escaped        => escaped = false,
+            '\\' if in_string   => escaped = true,
+            '"'                 => in_string = !in_string,
+            ';' if !in_string   => return &text[..i],
+            _                   => (),
+        }
+    }
+    text
+}
+
+fn parse_statement(isa: &InstructionSet, line: usize, text: &str) -> Result<Statement, AsmError> {
+    let (mnemonic, rest) = match text.find(char::is_whitespace) {
+        Some(i) => (&text[..i], &text[i..]),
+        None    => (text, ""),
+    };
+    let operands = split_operands(line, rest)?;
+
+    if mnemonic.eq_ignore_ascii_case("DW") {
+        let mut values = vec![];
+        for operand in &operands {
+            if operand.starts_with('"') {
+                values.extend(parse_string(line, operand)?);
+            }
+            else {
+                values.push(Expr::parse(line, operand)?);
+            }
+        }
+        return Ok(Statement::Data(values));
+    }
+
+    let op = isa.by_name(mnemonic).ok_or_else(|| AsmError::UnknownMnemonic(line, mnemonic.to_owned()))?;
+    if operands.len() != op.params {
+        return Err(AsmError::OperandCount(line, op.name.to_owned(), op.params, operands.len()));
+    }
+    let operands = operands
+        .iter()
+        .map(|operand| Operand::parse(line, operand))
+        .collect::<Result<Vec<_>, _>>()?;
+    if let Some(i) = op.writes {
+        if operands[i - 1].mode == Mode::Immediate {
+            return Err(AsmError::ImmediateDestination(line, i, op.name.to_owned()));
+        }
+    }
+    Ok(Statement::Instruction(op, operands))
+}
+
+//
+// assembler
+//
+
+pub fn assemble_program(source: &str) -> Result<Vec<i128>, AsmError> {
+    assemble_program_with(&isa::STANDARD, source)
+}
+
+// the same, with the mnemonics of the instruction set as well
+pub fn assemble_program_with(isa: &InstructionSet, source: &str) -> Result<Vec<i128>, AsmError> {
+    let mut labels = HashMap::<String, usize>::new();
+    let mut statements = vec![];
+    let mut addr = 0;
+
+    // pass 1: parse statements and assign addresses to labels
+    for (i, text) in source.lines().enumerate() {
+        let line = i + 1;
+        let mut text = strip_comment(text).trim();
+        while let Some(colon) = text.find(':').filter(|&c| !text[..c].contains('"')) {
+            let name = text[..colon].trim();
+            if !is_identifier(name) {
+                return Err(AsmError::BadLabel(line, name.to_owned()));
+            }
+            if labels.insert(name.to_owned(), addr).is_some() {
+                return Err(AsmError::DuplicateLabel(line, name.to_owned()));
+            }
+            text = text[colon+1..].trim();
+        }
+        if text.is_empty() {
+            continue;
+        }
+        let statement = parse_statement(isa, line, text)?;
+        addr += statement.size();
+        statements.push((line, statement));
+    }
+
+    // pass 2: resolve labels and encode
+    let mut program = Vec::with_capacity(addr);
+    for (line, statement) in &statements {
+        match statement {
+            Statement::Instruction(op, operands) => {
+                let modes = operands
+                    .iter()
+                    .enumerate()
+                    .map(|(i, operand)| 10_i128.pow(i as u32 + 2) * match operand.mode {
+                        Mode::Position  => 0,
+                        Mode::Immediate => 1,
+                        Mode::Relative  => 2,
+                    })
+                    .sum::<i128>();
+                program.push(op.code + modes);
+                for operand in operands {
+                    program.push(operand.expr.eval(*line, &labels)?);
+                }
+            },
+            Statement::Data(values) => {
+                for value in values {
+                    program.push(value.eval(*line, &labels)?);
+                }
+            },
+        }
+    }
+
+    Ok(program)
+}
+
+pub fn assemble(source: &str) -> Result<VM, AsmError> {
+    Ok(VM::new(&assemble_program(source)?))
+}
+
+pub fn assemble_with(source: &str, isa: Arc<InstructionSet>) -> Result<VM, AsmError> {
+    Ok(VM::new(&assemble_program_with(&isa, source)?).with_isa(isa))
+}
+
+//
+// tests
+//
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::intcode::disasm;
+    use crate::util;
+
+    #[test]
+    fn test_asm_encoding() -> Result<(), AsmError> {
+        let program = assemble_program("
+            BP   1
+            OUT  [bp-1]     ; relative mode
+            MUL  [4], 3, [4]
+            JNZ  1, end
+            end: HLT
+        ")?;
+        assert_eq!(program, &[109, 1, 204, -1, 1002, 4, 3, 4, 1105, 1, 11, 99]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_asm_quine() -> Result<(), AsmError> {
+        // day 9 example 1, written with labels
+        let mut vm = assemble("
+            start:  BP   1
+                    OUT  [bp-1]
+                    ADD  [counter], 1, [counter]
+                    EQ   [counter], 16, [flag]
+                    JZ   [flag], start
+                    HLT
+            counter: DW 0
+            flag:    DW 0
+        ")?;
+        assert_eq!(vm.memory, &[109, 1, 204, -1, 1001, 16, 1, 16, 1008, 16, 16, 17, 1006, 17, 0, 99, 0, 0]);
+        let mut vm2 = VM::new(&[109, 1, 204, -1, 1001, 100, 1, 100, 1008, 100, 16, 101, 1006, 101, 0, 99]);
+        assert_eq!(vm.run(&mut &[][..]).unwrap().len(), 16);
+        assert_eq!(vm2.run(&mut &[][..]).unwrap().len(), 16);
+        Ok(())
+    }
+
+    #[test]
+    fn test_asm_strings() -> Result<(), AsmError> {
+        let program = assemble_program(r#"
+            DW "a;b", 10, "\"\n", msg+1
+            msg: DW 0
+        "#)?;
+        assert_eq!(program, &[97, 59, 98, 10, 34, 10, 8, 0]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_asm_errors() {
+        assert_eq!(assemble_program("FOO 1"), Err(AsmError::UnknownMnemonic(1, "FOO".into())));
+        assert_eq!(assemble_program("ADD 1, 2"), Err(AsmError::OperandCount(1, "ADD".into(), 3, 2)));
+        assert_eq!(assemble_program("IN 1"), Err(AsmError::ImmediateDestination(1, 1, "IN".into())));
+        assert_eq!(assemble_program("\nJNZ 1, nowhere"), Err(AsmError::UndefinedLabel(2, "nowhere".into())));
+        assert_eq!(assemble_program("x: HLT\nx: HLT"), Err(AsmError::DuplicateLabel(2, "x".into())));
+        assert_eq!(assemble_program("DW \"abc"), Err(AsmError::UnterminatedString(1)));
+    }
+
+    #[test]
+    fn test_asm_roundtrip() -> Result<(), Box<dyn std::error::Error>> {
+        for day in &[2, 5, 9, 13, 17, 21, 25] {
+            let vm = util::get_parsed_line::<VM>(&format!("input/day{:02}.txt", day))?;
+            let source = disasm::Listing::from(&vm).to_string();
+            assert_eq!(assemble_program(&source)?, vm.memory);
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,259 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::convert::TryFrom;
+use std::fmt;
+use std::ops::Range;
+
+use crate::intcode::Instruction;
+use crate::intcode::disasm::{Item, Listing};
+
+//
+// struct Block -- straight-line code, entered at the top and left at the bottom
+//
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Block {
+    pub start: usize,
+    pub instrs: Vec<Instruction>,
+    // ends in a jump to an address only known at run time, like a return
+    pub indirect: bool,
+}
+
+impl Block {
+    pub fn end(&self) -> usize {
+        self.instrs.last().map_or(self.start, Instruction::end)
+    }
+}
+
+//
+// struct Edge
+//
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EdgeKind {
+    // on to the next instruction
+    Next,
+    // a jump to an immediate address
+    Jump,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Edge {
+    pub from: usize,
+    pub to: usize,
+    pub kind: EdgeKind,
+}
+
+//
+// struct Cfg -- the basic blocks of a program and the ways between them
+//
+
+// Built on the code the disassembler finds from address 0, which includes the return addresses
+// pushed by calls. Those are entry points of their own: returns are indirect, so no edge leads there.
+pub struct Cfg {
+    pub listing: Listing,
+    pub blocks: BTreeMap<usize, Block>,
+    pub edges: Vec<Edge>,
+    // cells that are part of an instruction; the rest is data
+    code: Vec<bool>,
+}
+
+impl Cfg {
+    fn with_listing(listing: Listing, len: usize) -> Self {
+        let instrs = listing.items
+            .iter()
+            .filter_map(|item| match item {
+                Item::Code(instr) => Some(instr),
+                Item::Data(_, _)  => None,
+            })
+            .collect::<Vec<_>>();
+
+        let mut code = vec![false; len];
+        for instr in &instrs {
+            code[instr.addr..instr.end()].iter_mut().for_each(|c| *c = true);
+        }
+
+        // blocks start at entry points and jump targets, and after jumps and halts
+        let mut leaders = listing.returns.clone();
+        leaders.insert(0);
+        for instr in &instrs {
+            if instr.op.jumps.is_some() || !instr.falls_through() {
+                leaders.insert(instr.end());
+            }
+            if let Some(target) = instr.target().and_then(|t| usize::try_from(t).ok()) {
+                leaders.insert(target);
+            }
+        }
+
+        let mut blocks = BTreeMap::<usize, Block>::new();
+        for instr in instrs {
+            match blocks.values_mut().next_back() {
+                Some(block) if block.end() == instr.addr && !leaders.contains(&instr.addr) => block.instrs.push(instr.clone()),
+                _                                                                          => { blocks.insert(instr.addr, Block { start: instr.addr, instrs: vec![instr.clone()], indirect: false }); },
+            }
+        }
+
+        let mut edges = vec![];
+        for block in blocks.values_mut() {
+            let last = &block.instrs[block.instrs.len() - 1];
+            block.indirect = last.op.jumps.is_some() && last.target().is_none();
+            if let Some(to) = last.target().and_then(|t| usize::try_from(t).ok()) {
+                edges.push(Edge { from: block.start, to, kind: EdgeKind::Jump });
+            }
+            if last.falls_through() {
+                edges.push(Edge { from: block.start, to: last.end(), kind: EdgeKind::Next });
+            }
+        }
+        // jumps out of the program, or into the middle of an instruction
+        edges.retain(|edge| blocks.contains_key(&edge.to));
+
+        Cfg { listing, blocks, edges, code }
+    }
+
+    pub fn is_code(&self, addr: usize) -> bool {
+        self.code.get(addr).copied().unwrap_or(false)
+    }
+
+    // address 0 and the return addresses of calls
+    pub fn entries(&self) -> BTreeSet<usize> {
+        let mut entries = self.listing.returns.clone();
+        entries.insert(0);
+        entries.retain(|addr| self.blocks.contains_key(addr));
+        entries
+    }
+
+    // runs of cells that are not code
+    pub fn data(&self) -> Vec<Range<usize>> {
+        let mut ranges = Vec::<Range<usize>>::new();
+        for addr in (0..self.code.len()).filter(|&addr| !self.is_code(addr)) {
+            match ranges.last_mut() {
+                Some(range) if range.end == addr => range.end += 1,
+                _                                => ranges.push(addr..addr + 1),
+            }
+        }
+        ranges
+    }
+
+    pub fn dot(&self) -> Dot<'_> {
+        Dot(self)
+    }
+}
+
+impl From<&crate::intcode::VM> for Cfg {
+    fn from(vm: &crate::intcode::VM) -> Self {
+        // the VM knows which opcodes it has, custom ones included
+        Cfg::with_listing(Listing::from(vm), vm.memory.len())
+    }
+}
+
+//
+// struct Dot -- the graph in Graphviz format
+//
+
+// Entry points are drawn bold, indirect jumps as dashed edges to a node "?". Data is listed in
+// comments, as it is not part of the graph.
+pub struct Dot<'a>(&'a Cfg);
+
+impl fmt::Display for Dot<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let cfg = self.0;
+        let entries = cfg.entries();
+        writeln!(f, "digraph intcode {{")?;
+        writeln!(f, "    node [shape=box, fontname=\"monospace\"];")?;
+        for range in cfg.data() {
+            writeln!(f, "    // data {:04}..{:04}", range.start, range.end)?;
+        }
+        for block in cfg.blocks.values() {
+            let text = block.instrs
+                .iter()
+                .map(|instr| format!("{:04}  {}\\l", instr.addr, cfg.listing.text(instr)))
+                .collect::<String>();
+            let style = if entries.contains(&block.start) { ", style=bold" } else { "" };
+            writeln!(f, "    L{0:04} [label=\"L{0:04}:\\l{1}\"{2}];", block.start, text, style)?;
+        }
+        if cfg.blocks.values().any(|block| block.indirect) {
+            writeln!(f, "    indirect [shape=diamond, label=\"?\"];")?;
+        }
+        for edge in &cfg.edges {
+            match edge.kind {
+                EdgeKind::Next => writeln!(f, "    L{:04} -> L{:04};", edge.from, edge.to)?,
+                EdgeKind::Jump => writeln!(f, "    L{:04} -> L{:04} [label=\"jump\"];", edge.from, edge.to)?,
+            }
+        }
+        for block in cfg.blocks.values().filter(|block| block.indirect) {
+            writeln!(f, "    L{:04} -> indirect [style=dashed];", block.start)?;
+        }
+        writeln!(f, "}}")
+    }
+}
+
+//
+// tests
+//
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use std::sync::Arc;
+
+    use crate::intcode::{Opcode, VM};
+    use crate::intcode::isa::{Action, InstructionSet};
+    use crate::util;
+
+    fn edges(cfg: &Cfg) -> Vec<(usize, usize, EdgeKind)> {
+        cfg.edges.iter().map(|e| (e.from, e.to, e.kind)).collect()
+    }
+
+    #[test]
+    fn test_cfg_branch() {
+        // JZ over an OUT, then halt, followed by data
+        let cfg = Cfg::from(&VM::new(&[3, 9, 1006, 9, 7, 4, 9, 99, 1, 2]));
+        assert_eq!(cfg.blocks.keys().copied().collect::<Vec<_>>(), [0, 5, 7]);
+        assert_eq!(edges(&cfg), [(0, 7, EdgeKind::Jump), (0, 5, EdgeKind::Next), (5, 7, EdgeKind::Next)]);
+        assert_eq!(cfg.data(), vec![Range { start: 8, end: 10 }]);
+        assert!(cfg.is_code(7) && !cfg.is_code(8));
+        assert!(cfg.blocks.values().all(|block| !block.indirect));
+    }
+
+    #[test]
+    fn test_cfg_call() {
+        // push return address 9, jump to subroutine at 11, which returns through [bp+0]
+        let cfg = Cfg::from(&VM::new(&[109, 20, 21101, 0, 9, 0, 1105, 1, 11, 99, 0, 104, 42, 2105, 1, 0]));
+        assert_eq!(cfg.blocks.keys().copied().collect::<Vec<_>>(), [0, 9, 11]);
+        assert_eq!(edges(&cfg), [(0, 11, EdgeKind::Jump)]);
+        assert_eq!(cfg.entries().into_iter().collect::<Vec<_>>(), [0, 9]);
+        assert!(cfg.blocks[&11].indirect);
+        let dot = cfg.dot().to_string();
+        assert!(dot.contains("    L0009 [label=\"L0009:\\l0009  HLT\\l\", style=bold];\n"));
+        assert!(dot.contains("    L0000 -> L0011 [label=\"jump\"];\n"));
+        assert!(dot.contains("    L0011 -> indirect [style=dashed];\n"));
+        assert!(dot.contains("    // data 0010..0011\n"));
+    }
+
+    #[test]
+    fn test_cfg_custom() -> Result<(), Box<dyn std::error::Error>> {
+        // squares 3 into the last cell and outputs it; without SQR, nothing here is code
+        static SQR: Opcode = Opcode { code: 20, name: "SQR", params: 2, writes: Some(2), jumps: None };
+        let isa = InstructionSet::standard().with(&SQR, |args| Action::Store(args[0] * args[0]))?;
+        let vm = VM::new(&[120, 3, 6, 4, 6, 99, 0]).with_isa(Arc::new(isa));
+        let cfg = Cfg::from(&vm);
+        assert_eq!(cfg.blocks[&0].instrs.len(), 3);
+        assert_eq!(cfg.data(), vec![Range { start: 6, end: 7 }]);
+        assert!(!Cfg::from(&VM::new(&vm.memory.to_vec())).is_code(0));
+        Ok(())
+    }
+
+    #[test]
+    fn test_cfg_puzzles() -> Result<(), Box<dyn std::error::Error>> {
+        for day in &[19, 21] {
+            let vm = util::get_parsed_line::<VM>(&format!("input/day{:02}.txt", day))?;
+            let cfg = Cfg::from(&vm);
+            assert!(cfg.edges.iter().all(|edge| cfg.blocks.contains_key(&edge.from) && cfg.blocks.contains_key(&edge.to)));
+            let size = cfg.blocks.values().map(|block| block.end() - block.start).sum::<usize>()
+                + cfg.data().iter().map(|range| range.len()).sum::<usize>();
+            assert_eq!(size, vm.memory.len());
+            assert!(cfg.blocks.values().any(|block| block.indirect));
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,198 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::convert::TryFrom;
+use std::fmt;
+
+use crate::intcode::{Instruction, Mode, VM};
+use crate::intcode::disasm::{Item, Listing};
+use crate::intcode::trace::Tracer;
+
+//
+// enum Warning
+//
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Warning {
+    // an instruction ran from a cell the program wrote to
+    ExecuteAfterWrite { ip: usize, addr: usize, writer: usize },
+    // a read past the program image, of a cell that was never written
+    UninitializedRead { ip: usize, addr: usize },
+    // a write to a cell that an instruction uses as an immediate operand
+    ImmediateWrite { ip: usize, addr: usize, instr: usize },
+}
+
+impl fmt::Display for Warning {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Warning::ExecuteAfterWrite { ip, addr, writer } => write!(f, "{:04}: executes {:04}, written by {:04}", ip, addr, writer),
+            Warning::UninitializedRead { ip, addr }         => write!(f, "{:04}: reads {:04}, never written", ip, addr),
+            Warning::ImmediateWrite { ip, addr, instr }     => write!(f, "{:04}: writes {:04}, an immediate operand of {:04}", ip, addr, instr),
+        }
+    }
+}
+
+//
+// struct Checker -- records how each cell is used, and warns about suspicious accesses
+//
+
+// Opt in by running with run_traced. Immediate operands are known from the disassembly of the
+// image and from every instruction executed, so patching one is caught even before it runs.
+pub struct Checker {
+    image: usize,
+    pub executed: HashSet<usize>,
+    pub read: HashSet<usize>,
+    // by the address of the instruction that wrote last
+    pub written: HashMap<usize, usize>,
+    // by the address of the instruction they belong to
+    immediates: HashMap<usize, usize>,
+    // how often each one happened
+    pub warnings: BTreeMap<Warning, u64>,
+    memory: usize,
+}
+
+impl Checker {
+    pub fn new(vm: &VM) -> Self {
+        let mut checker = Checker {
+            image: vm.memory.len(),
+            executed: HashSet::new(),
+            read: HashSet::new(),
+            written: HashMap::new(),
+            immediates: HashMap::new(),
+            warnings: BTreeMap::new(),
+            memory: vm.memory.len(),
+        };
+        for item in Listing::from(vm).items {
+            if let Item::Code(instr) = item {
+                checker.add_immediates(&instr);
+            }
+        }
+        checker
+    }
+
+    fn add_immediates(&mut self, instr: &Instruction) {
+        for (i, &mode) in instr.modes.iter().enumerate() {
+            if mode == Mode::Immediate {
+                self.immediates.insert(instr.addr + i + 1, instr.addr);
+            }
+        }
+    }
+
+    fn warn(&mut self, warning: Warning) {
+        *self.warnings.entry(warning).or_insert(0) += 1;
+    }
+}
+
+impl Tracer for Checker {
+    fn trace(&mut self, vm: &VM, instr: &Instruction, values: &[i128]) {
+        self.memory = self.memory.max(vm.memory.len());
+        for addr in instr.addr..instr.end() {
+            self.executed.insert(addr);
+            if let Some(&writer) = self.written.get(&addr) {
+                self.warn(Warning::ExecuteAfterWrite { ip: instr.addr, addr, writer });
+            }
+        }
+        self.add_immediates(instr);
+
+        for (i, (&mode, &param)) in instr.modes.iter().zip(&instr.params).enumerate() {
+            let addr = match mode {
+                _ if instr.op.writes == Some(i + 1) => continue,
+                Mode::Immediate                     => continue,
+                Mode::Position                      => usize::try_from(param).ok(),
+                // an address that overflows is the VM's error to report, not a read
+                Mode::Relative                      => vm.bp.checked_add(param).and_then(|addr| usize::try_from(addr).ok()),
+            };
+            if let Some(addr) = addr {
+                self.read.insert(addr);
+                if addr >= self.image && !self.written.contains_key(&addr) {
+                    self.warn(Warning::UninitializedRead { ip: instr.addr, addr });
+                }
+            }
+        }
+
+        if let Some(addr) = instr.op.writes.and_then(|i| usize::try_from(values[i - 1]).ok()) {
+            if let Some(&owner) = self.immediates.get(&addr) {
+                self.warn(Warning::ImmediateWrite { ip: instr.addr, addr, instr: owner });
+            }
+            self.written.insert(addr, instr.addr);
+        }
+    }
+}
+
+impl fmt::Display for Checker {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "image {} cells, memory grew to {}", self.image, self.memory)?;
+        writeln!(f, "cells executed {}, read {}, written {}", self.executed.len(), self.read.len(), self.written.len())?;
+        if self.warnings.is_empty() {
+            return writeln!(f, "no warnings");
+        }
+        writeln!(f, "warnings:")?;
+        for (warning, count) in &self.warnings {
+            writeln!(f, "{:>8}x  {}", count, warning)?;
+        }
+        Ok(())
+    }
+}
+
+//
+// tests
+//
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::intcode::VmError;
+    use crate::util;
+
+    fn check(program: &[i128]) -> Vec<Warning> {
+        let mut vm = VM::new(program);
+        let mut checker = Checker::new(&vm);
+        vm.run_traced(&mut &[][..], &mut checker).unwrap();
+        checker.warnings.keys().copied().collect()
+    }
+
+    #[test]
+    fn test_check_execute_after_write() {
+        assert_eq!(check(&[1002, 4, 3, 4, 33]), [Warning::ExecuteAfterWrite { ip: 4, addr: 4, writer: 0 }]);
+    }
+
+    #[test]
+    fn test_check_uninitialized_read() {
+        assert_eq!(check(&[4, 100, 99]), [Warning::UninitializedRead { ip: 0, addr: 100 }]);
+        assert_eq!(check(&[1101, 1, 2, 100, 4, 100, 99]), []);
+    }
+
+    #[test]
+    fn test_check_immediate_write() {
+        // patches the target of the jump at 4 before it runs
+        assert_eq!(check(&[1101, 0, 9, 6, 1105, 1, 0, 99, 99, 99]), [
+            Warning::ExecuteAfterWrite { ip: 4, addr: 6, writer: 0 },
+            Warning::ImmediateWrite { ip: 0, addr: 6, instr: 4 },
+        ]);
+    }
+
+    #[test]
+    fn test_check_overflow() {
+        let mut vm = VM::new(&[109, 1, 204, i128::MAX, 99]);
+        let mut checker = Checker::new(&vm);
+        assert_eq!(vm.run_traced(&mut &[][..], &mut checker).unwrap_err(), VmError::Overflow { ip: 2, bp: 1, instr: 204 });
+        assert!(checker.warnings.is_empty());
+    }
+
+    #[test]
+    fn test_check_puzzles() -> Result<(), Box<dyn std::error::Error>> {
+        let mut vm = util::get_parsed_line::<VM>("input/day09.txt")?;
+        let mut checker = Checker::new(&vm);
+        vm.run_traced(&mut &[1][..], &mut checker)?;
+        assert!(checker.warnings.is_empty());
+        assert!(checker.to_string().ends_with("no warnings\n"));
+
+        // day 19 calls through a jump whose target it patches first
+        let mut vm = util::get_parsed_line::<VM>("input/day19.txt")?;
+        let mut checker = Checker::new(&vm);
+        vm.run_traced(&mut &[5, 5][..], &mut checker)?;
+        assert_eq!(checker.warnings.get(&Warning::ExecuteAfterWrite { ip: 247, addr: 249, writer: 227 }), Some(&5));
+        assert_eq!(checker.warnings.get(&Warning::ImmediateWrite { ip: 227, addr: 249, instr: 247 }), Some(&5));
+        assert!(checker.to_string().contains("       5x  0247: executes 0249, written by 0227\n"));
+        Ok(())
+    }
+}
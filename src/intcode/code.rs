@@ -0,0 +1,126 @@
+//
+// enum Op -- what an instruction does
+//
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Op {
+    Add,
+    Mul,
+    In,
+    Out,
+    Jnz,
+    Jz,
+    Lt,
+    Eq,
+    Bp,
+    Halt,
+    Unknown,
+}
+
+//
+// struct Decoded -- an instruction word split into its operation and parameter modes
+//
+
+// Modes are kept as raw digits, so that a bad one is only reported when its parameter is used,
+// just like when decoding on the fly.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Decoded {
+    pub op: Op,
+    pub modes: [u8; 3],
+}
+
+impl Decoded {
+    pub fn new(instr: i128) -> Self {
+        let op = match instr % 100 {
+            1  => Op::Add,
+            2  => Op::Mul,
+            3  => Op::In,
+            4  => Op::Out,
+            5  => Op::Jnz,
+            6  => Op::Jz,
+            7  => Op::Lt,
+            8  => Op::Eq,
+            9  => Op::Bp,
+            99 => Op::Halt,
+            _  => Op::Unknown,
+        };
+        let mode = |i: u32| (instr / 10_i128.pow(i + 1) % 10) as u8;
+        Decoded { op, modes: [mode(1), mode(2), mode(3)] }
+    }
+}
+
+//
+// struct Code -- cache of decoded instruction words, by address
+//
+
+// An entry is dropped whenever its cell is written to, so self-modifying code is decoded again.
+// Parameters are not cached; they are read from memory every time.
+#[derive(Debug, Default)]
+pub struct Code {
+    decoded: Vec<Option<Decoded>>,
+}
+
+impl Code {
+    #[inline]
+    pub fn get(&self, addr: usize) -> Option<Decoded> {
+        self.decoded.get(addr).copied().flatten()
+    }
+
+    // limit is how far the cache may grow, so that code in sparse memory is not cached
+    pub fn insert(&mut self, addr: usize, decoded: Decoded, limit: usize) {
+        if addr >= limit {
+            return;
+        }
+        if addr >= self.decoded.len() {
+            self.decoded.resize(limit, None);
+        }
+        self.decoded[addr] = Some(decoded);
+    }
+
+    #[inline]
+    pub fn invalidate(&mut self, addr: usize) {
+        if let Some(slot) = self.decoded.get_mut(addr) {
+            *slot = None;
+        }
+    }
+
+    pub fn truncate(&mut self, len: usize) {
+        self.decoded.truncate(len);
+    }
+}
+
+// decoding again is cheaper than copying the cache for every clone of a VM
+impl Clone for Code {
+    fn clone(&self) -> Self {
+        Code::default()
+    }
+}
+
+//
+// tests
+//
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_code_decode() {
+        assert_eq!(Decoded::new(21101), Decoded { op: Op::Add, modes: [1, 1, 2] });
+        assert_eq!(Decoded::new(1000004), Decoded { op: Op::Out, modes: [0, 0, 0] });
+        assert_eq!(Decoded::new(399), Decoded { op: Op::Halt, modes: [3, 0, 0] });
+        assert_eq!(Decoded::new(-1), Decoded { op: Op::Unknown, modes: [0, 0, 0] });
+    }
+
+    #[test]
+    fn test_code_cache() {
+        let mut code = Code::default();
+        code.insert(2, Decoded::new(1), 10);
+        code.insert(20, Decoded::new(2), 10);
+        assert_eq!(code.get(2), Some(Decoded::new(1)));
+        assert_eq!(code.get(20), None);
+        assert_eq!(code.clone().get(2), None);
+        code.invalidate(2);
+        assert_eq!(code.get(2), None);
+    }
+}
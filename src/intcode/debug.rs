@@ -0,0 +1,384 @@
+use std::collections::{BTreeMap, BTreeSet, VecDeque};
+use std::io::{BufRead, Write};
+
+use crate::intcode::{Instruction, Status, VM};
+use crate::intcode::diff::Diff;
+use crate::intcode::reverse::{Journal, Undone};
+use crate::intcode::snapshot::Snapshot;
+
+// number of instructions the debugger can step back
+const HISTORY: usize = 1_000_000;
+
+// most memory cells a single examine command shows
+const EXAMINE_LIMIT: usize = 4096;
+
+static HELP: &str = "\
+s [n]           step n instructions (default 1)
+c               continue until breakpoint, watchpoint, input wait or halt
+rs [n]          step back n instructions (default 1)
+rw addr         run back to the last write of a memory cell
+rewind n        run back to instruction count n
+b [addr]        set breakpoint on IP, or list breakpoints and watchpoints
+w addr          set watchpoint on memory cell
+d addr          delete breakpoint or watchpoint
+r               show IP, BP and instruction count
+l [addr] [n]    list n instructions from addr (default IP)
+x addr [n]      examine n memory cells from addr
+i n...          queue numbers as input
+a text          queue text plus newline as ASCII input
+o               show and clear queued output
+save file       write VM state and pending I/O to a snapshot file
+load file       restore a snapshot file (clears history)
+diff file       show what changed since a snapshot file
+q               quit";
+
+//
+// struct Debugger
+//
+
+#[derive(Debug)]
+pub struct Debugger {
+    pub vm: VM,
+    pub input: VecDeque<i128>,
+    pub output: Vec<i128>,
+    pub steps: u64,
+    breakpoints: BTreeSet<usize>,
+    watchpoints: BTreeMap<usize, i128>,
+    journal: Journal,
+    halted: bool,
+}
+
+impl Debugger {
+    pub fn new(vm: VM) -> Self {
+        Debugger {
+            vm,
+            input: VecDeque::new(),
+            output: vec![],
+            steps: 0,
+            breakpoints: BTreeSet::new(),
+            watchpoints: BTreeMap::new(),
+            journal: Journal::new(HISTORY),
+            halted: false,
+        }
+    }
+
+    pub fn from_snapshot(snapshot: Snapshot) -> Self {
+        let mut debugger = Debugger::new(snapshot.vm);
+        debugger.input.extend(snapshot.input);
+        debugger.output = snapshot.output;
+        debugger
+    }
+
+    pub fn snapshot(&self) -> Snapshot {
+        Snapshot::new(&self.vm, &self.input.iter().copied().collect::<Vec<_>>(), &self.output)
+    }
+
+    pub fn repl<R: BufRead, W: Write>(&mut self, input: R, mut out: W) -> std::io::Result<()> {
+        write!(out, "{}\n(idb) ", self.location())?;
+        out.flush()?;
+        for line in input.lines() {
+            if !self.command(&line?, &mut out)? {
+                break;
+            }
+            write!(out, "(idb) ")?;
+            out.flush()?;
+        }
+        Ok(())
+    }
+
+    // executes one debugger command; returns false when the session should end
+    pub fn command<W: Write>(&mut self, line: &str, out: &mut W) -> std::io::Result<bool> {
+        let mut words = line.split_whitespace();
+        let cmd = words.next().unwrap_or("");
+        let args = words.map(parse_number).collect::<Option<Vec<i128>>>();
+
+        match (cmd, args.as_deref()) {
+            ("", _)                         => (),
+            ("q", _)                        => return Ok(false),
+            ("h", _) | ("?", _)             => writeln!(out, "{}", HELP)?,
+            ("s", Some([]))                 => self.run(out, Some(1))?,
+            ("s", Some([n])) if *n > 0      => self.run(out, Some(*n as u64))?,
+            ("c", Some([]))                 => self.run(out, None)?,
+            ("rs", Some([]))                => self.reverse(out, |_| true, 1)?,
+            ("rs", Some([n])) if *n > 0     => self.reverse(out, |_| true, *n as u64)?,
+            ("rw", Some([a])) if *a >= 0    => self.reverse(out, |undone| undone.write == Some(*a as usize), 1)?,
+            ("rewind", Some([n])) if *n >= 0 => { let n = (*n as u64).max(self.journal.horizon());
+                                                  let count = self.steps.saturating_sub(n);
+                                                  self.reverse(out, |_| true, count)? },
+            ("b", Some([]))                 => { writeln!(out, "breakpoints: {:?}", self.breakpoints)?;
+                                                 writeln!(out, "watchpoints: {:?}", self.watchpoints.keys().collect::<Vec<_>>())?; },
+            ("b", Some([a])) if *a >= 0     => { self.breakpoints.insert(*a as usize); },
+            ("w", Some([a])) if *a >= 0     => { let value = self.vm.memory.get(*a as usize).copied().unwrap_or(0);
+                                                 self.watchpoints.insert(*a as usize, value); },
+            ("d", Some([a])) if *a >= 0     => { self.breakpoints.remove(&(*a as usize));
+                                                 self.watchpoints.remove(&(*a as usize)); },
+            ("r", Some([]))                 => writeln!(out, "IP={} BP={} steps={} input={:?}", self.vm.ip, self.vm.bp, self.steps, self.input)?,
+            ("l", Some(args))               => self.list(out, args)?,
+            ("x", Some([a])) if *a >= 0     => self.examine(out, *a as usize, 1)?,
+            ("x", Some([a, n])) if *a >= 0 && *n >= 0 => self.examine(out, *a as usize, *n as usize)?,
+            ("i", Some(values))             => self.input.extend(values),
+            ("save", _)                     => { let filename = line.trim_start()[4..].trim();
+                                                 match self.snapshot().save(filename) {
+                                                     Ok(())  => writeln!(out, "saved to {}", filename)?,
+                                                     Err(e)  => writeln!(out, "error: {}", e)?,
+                                                 } },
+            ("load", _)                     => { let filename = line.trim_start()[4..].trim();
+                                                 match Snapshot::load(filename) {
+                                                     Ok(snapshot) => { let breakpoints = std::mem::take(&mut self.breakpoints);
+                                                                       let isa = self.vm.isa.clone();
+                                                                       *self = Debugger::from_snapshot(snapshot);
+                                                                       self.vm.isa = isa;
+                                                                       self.breakpoints = breakpoints;
+                                                                       writeln!(out, "{}", self.location())? },
+                                                     Err(e)       => writeln!(out, "error: {}", e)?,
+                                                 } },
+            ("diff", _)                     => { let filename = line.trim_start()[4..].trim();
+                                                 match Snapshot::load(filename) {
+                                                     Ok(snapshot) => write!(out, "{}", Diff::new(&snapshot.vm, &self.vm))?,
+                                                     Err(e)       => writeln!(out, "error: {}", e)?,
+                                                 } },
+            ("a", _)                        => { let text = line.trim_start()[1..].trim_start();
+                                                 self.input.extend(text.bytes().map(|b| b as i128));
+                                                 self.input.push_back(10); },
+            ("o", Some([]))                 => { writeln!(out, "{:?}", self.output)?;
+                                                 if let Some(text) = ascii(&self.output) {
+                                                     write!(out, "{}", text)?;
+                                                 }
+                                                 self.output.clear(); },
+            _                               => writeln!(out, "?? {:?} (h for help)", line)?,
+        }
+        Ok(true)
+    }
+
+    fn run<W: Write>(&mut self, out: &mut W, count: Option<u64>) -> std::io::Result<()> {
+        if self.halted {
+            return writeln!(out, "program has halted");
+        }
+        let mut remaining = count;
+        loop {
+            let input = &mut self.input;
+            let output = &mut self.output;
+            match self.vm.tick_recorded(&mut self.journal, &mut || input.pop_front(), &mut |v| output.push(v)) {
+                Err(e)                      => { return writeln!(out, "error: {}", e) },
+                Ok(Some(Status::Halted))    => { self.halted = true;
+                                                 return writeln!(out, "halted after {} steps", self.steps) },
+                Ok(Some(Status::Blocked))   => { return writeln!(out, "waiting for input\n{}", self.location()) },
+                Ok(Some(Status::Suspended)) => { self.steps += 1;
+                                                 writeln!(out, "output: {}", self.output.last().unwrap())?; },
+                Ok(None)                    => { self.steps += 1; },
+            }
+
+            let mut stop = false;
+            for (&addr, value) in self.watchpoints.iter_mut() {
+                let current = self.vm.memory.get(addr).copied().unwrap_or(0);
+                if current != *value {
+                    writeln!(out, "watchpoint [{}]: {} -> {}", addr, value, current)?;
+                    *value = current;
+                    stop = true;
+                }
+            }
+            if self.breakpoints.contains(&self.vm.ip) {
+                writeln!(out, "breakpoint at {}", self.vm.ip)?;
+                stop = true;
+            }
+            if let Some(n) = remaining.as_mut() {
+                *n -= 1;
+                stop |= *n == 0;
+            }
+            if stop {
+                return writeln!(out, "{}", self.location());
+            }
+        }
+    }
+
+    // steps back until the predicate has matched count undone instructions
+    fn reverse<W: Write, P: Fn(&Undone) -> bool>(&mut self, out: &mut W, predicate: P, count: u64) -> std::io::Result<()> {
+        let mut remaining = count;
+        while remaining > 0 {
+            let undone = match self.vm.step_back(&mut self.journal) {
+                Some(undone) => undone,
+                None         => { writeln!(out, "no more history")?; break },
+            };
+            self.steps -= 1;
+            self.halted = false;
+            if let Some(v) = undone.input {
+                self.input.push_front(v);
+            }
+            if undone.output {
+                self.output.pop();
+            }
+            if predicate(&undone) {
+                remaining -= 1;
+            }
+        }
+        for (&addr, value) in self.watchpoints.iter_mut() {
+            *value = self.vm.memory.get(addr).copied().unwrap_or(0);
+        }
+        writeln!(out, "{}", self.location())
+    }
+
+    fn list<W: Write>(&self, out: &mut W, args: &[i128]) -> std::io::Result<()> {
+        let mut addr = args.first().map_or(self.vm.ip, |&a| a.max(0) as usize);
+        for _ in 0..args.get(1).map_or(10, |&n| n.max(0)) {
+            match Instruction::decode_with(&self.vm.isa, &self.vm.memory, addr) {
+                Some(instr) => { writeln!(out, "{} {:5}  {}", if addr == self.vm.ip { "=>" } else { "  " }, addr, instr)?;
+                                 addr = instr.end(); },
+                None        => { match self.vm.memory.get(addr) {
+                                     Some(v) => writeln!(out, "   {:5}  DW  {}", addr, v)?,
+                                     None    => break,
+                                 }
+                                 addr += 1; },
+            }
+        }
+        Ok(())
+    }
+
+    fn examine<W: Write>(&self, out: &mut W, addr: usize, count: usize) -> std::io::Result<()> {
+        let end = match addr.checked_add(count) {
+            Some(end) if count <= EXAMINE_LIMIT => end,
+            _                                   => return writeln!(out, "error: can show at most {} cells from an address", EXAMINE_LIMIT),
+        };
+        let values = (addr..end)
+            .map(|a| self.vm.memory.get(a).copied().unwrap_or(0).to_string())
+            .collect::<Vec<_>>();
+        writeln!(out, "[{}]: {}", addr, values.join(" "))
+    }
+
+    fn location(&self) -> String {
+        match Instruction::decode_with(&self.vm.isa, &self.vm.memory, self.vm.ip) {
+            Some(instr) => format!("=> {:5}  {}", self.vm.ip, instr),
+            None        => format!("=> {:5}  ???", self.vm.ip),
+        }
+    }
+}
+
+fn parse_number(word: &str) -> Option<i128> {
+    word.parse::<i128>().ok()
+}
+
+fn ascii(values: &[i128]) -> Option<String> {
+    if !values.is_empty() && values.iter().all(|&v| v == 10 || (32..127).contains(&v)) {
+        Some(values.iter().map(|&v| v as u8 as char).collect())
+    }
+    else {
+        None
+    }
+}
+
+//
+// tests
+//
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn session(vm: VM, commands: &[&str]) -> (Debugger, String) {
+        let mut debugger = Debugger::new(vm);
+        let mut out = vec![];
+        debugger.repl(commands.join("\n").as_bytes(), &mut out).unwrap();
+        (debugger, String::from_utf8(out).unwrap())
+    }
+
+    #[test]
+    fn test_debug_step() {
+        let (debugger, _) = session(VM::new(&[1101, 2, 3, 5, 99, 0]), &["s"]);
+        assert_eq!(debugger.vm.ip, 4);
+        assert_eq!(debugger.vm.memory[5], 5);
+        assert_eq!(debugger.steps, 1);
+    }
+
+    #[test]
+    fn test_debug_breakpoint() {
+        // day 5 example: output 1 if input equals 8
+        let vm = VM::new(&[3, 9, 8, 9, 10, 9, 4, 9, 99, -1, 8]);
+        let (debugger, out) = session(vm, &["b 6", "i 8", "c", "r"]);
+        assert_eq!(debugger.vm.ip, 6);
+        assert!(out.contains("breakpoint at 6"));
+        assert!(out.contains("IP=6 BP=0 steps=2"));
+    }
+
+    #[test]
+    fn test_debug_watchpoint_and_output() {
+        let vm = VM::new(&[3, 9, 8, 9, 10, 9, 4, 9, 99, -1, 8]);
+        let (debugger, out) = session(vm, &["w 9", "c", "i 7", "c", "c", "c", "o", "c"]);
+        assert!(out.contains("waiting for input"));
+        assert!(out.contains("watchpoint [9]: -1 -> 7"));
+        assert!(out.contains("watchpoint [9]: 7 -> 0"));
+        assert!(out.contains("output: 0"));
+        assert!(out.contains("[0]"));
+        assert!(out.contains("halted after 3 steps"));
+        assert!(out.contains("program has halted"));
+        assert!(debugger.output.is_empty());
+    }
+
+    #[test]
+    fn test_debug_list_and_examine() {
+        let vm = VM::new(&[109, 1, 204, -1, 99]);
+        let (_, out) = session(vm, &["l 0 3", "x 2 3", "a hi"]);
+        assert!(out.contains("=>     0  BP  1"));
+        assert!(out.contains("       2  OUT [bp-1]"));
+        assert!(out.contains("       4  HLT"));
+        assert!(out.contains("[2]: 204 -1 99"));
+
+        // bad counts are reported, not panicked on
+        let (_, out) = session(VM::new(&[99]), &["x 0 -1", "x 0 1000000000", "x 18446744073709551615 2"]);
+        assert!(out.contains("?? \"x 0 -1\""));
+        assert_eq!(out.matches("error: can show at most 4096 cells").count(), 2, "{}", out);
+    }
+
+    #[test]
+    fn test_debug_reverse() {
+        let vm = VM::new(&[3, 9, 8, 9, 10, 9, 4, 9, 99, -1, 8]);
+        let (debugger, out) = session(vm, &["i 8", "c", "rs", "r", "rw 9", "r", "x 9", "rw 9", "r", "x 9"]);
+        assert!(out.contains("output: 1"));
+        assert!(out.contains("IP=6 BP=0 steps=2 input=[]"));
+        assert!(out.contains("IP=2 BP=0 steps=1 input=[]"));
+        assert!(out.contains("[9]: 8"));
+        assert!(out.contains("IP=0 BP=0 steps=0 input=[8]"));
+        assert!(out.contains("[9]: -1"));
+        assert!(debugger.output.is_empty());
+    }
+
+    #[test]
+    fn test_debug_rewind() {
+        let vm = VM::new(&[1101, 1, 1, 0, 1101, 2, 2, 0, 1101, 3, 3, 0, 99]);
+        let (debugger, out) = session(vm, &["c", "rewind 1", "r", "x 0"]);
+        assert!(out.contains("IP=4 BP=0 steps=1"));
+        assert!(out.contains("[0]: 2"));
+        assert_eq!(debugger.vm.ip, 4);
+    }
+
+    #[test]
+    fn test_debug_error() {
+        let (_, out) = session(VM::new(&[42]), &["s"]);
+        assert!(out.contains("error: Unknown opcode 42 at IP=0, BP=0"));
+    }
+
+    #[test]
+    fn test_debug_save_load() {
+        let path = std::env::temp_dir().join(format!("idb-{}.icvm", std::process::id()));
+        let path = path.to_str().unwrap();
+        let vm = VM::new(&[3, 9, 8, 9, 10, 9, 4, 9, 99, -1, 8]);
+        let (saved, _) = session(vm.clone(), &["i 8 5", "s", &format!("save {}", path)]);
+        let (mut loaded, out) = session(vm, &[&format!("load {}", path), "r"]);
+        std::fs::remove_file(path).unwrap();
+        assert_eq!(loaded.snapshot(), saved.snapshot());
+        assert!(out.contains("IP=2 BP=0 steps=0 input=[5]"));
+        let mut out = vec![];
+        loaded.command("c", &mut out).unwrap();
+        assert!(String::from_utf8(out).unwrap().contains("output: 1"));
+        assert!(session(VM::new(&[99]), &["load /nonexistent/file"]).1.contains("error: "));
+    }
+
+    #[test]
+    fn test_debug_diff() {
+        let path = std::env::temp_dir().join(format!("idb-diff-{}.icvm", std::process::id()));
+        let path = path.to_str().unwrap();
+        let vm = VM::new(&[3, 9, 8, 9, 10, 9, 4, 9, 99, -1, 8]);
+        let (_, out) = session(vm, &["i 8", &format!("save {}", path), "s 2", &format!("diff {}", path)]);
+        std::fs::remove_file(path).unwrap();
+        assert!(out.contains("IP 0 -> 6\n"));
+        assert!(out.contains("\n     9                -1                1  data\n"), "{}", out);
+    }
+}
@@ -0,0 +1,699 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::convert::TryFrom;
+use std::fmt;
+
+use regex::Regex;
+
+use crate::intcode::{Instruction, Mode, VM};
+use crate::intcode::cfg::{Block, Cfg};
+
+const INDENT: usize = 4;
+
+lazy_static! {
+    static ref TEMPORARY: Regex = Regex::new(r"\bs\d+\b").unwrap();
+    static ref SIMPLE: Regex = Regex::new(r"^!?[\w\[\]]+$").unwrap();
+}
+
+//
+// enum Stmt -- a line or block of pseudocode
+//
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Stmt {
+    Assign(String, String),
+    Output(String),
+    // result, function, arguments
+    Call(Option<String>, String, Vec<String>),
+    Return(Option<String>),
+    Halt,
+    If(String, Vec<Stmt>, Vec<Stmt>),
+    While(String, Vec<Stmt>),
+    DoWhile(Vec<Stmt>, String),
+    Break,
+    Continue,
+    Goto(usize),
+    // a jump to an address read from memory
+    Computed(String),
+    Label(usize),
+    // anything else, like moving the relative base outside of a prologue or epilogue
+    Raw(String),
+}
+
+impl Stmt {
+    // whether control never goes on to the next statement
+    fn ends(&self) -> bool {
+        matches!(self, Stmt::Return(_) | Stmt::Halt | Stmt::Break | Stmt::Continue | Stmt::Goto(_) | Stmt::Computed(_))
+    }
+
+    fn write(&self, f: &mut fmt::Formatter, depth: usize) -> fmt::Result {
+        let pad = " ".repeat(depth * INDENT);
+        let block = |f: &mut fmt::Formatter, stmts: &[Stmt]| stmts.iter().try_for_each(|stmt| stmt.write(f, depth + 1));
+        match self {
+            Stmt::Assign(dst, expr)         => writeln!(f, "{}{} = {};", pad, dst, expr),
+            Stmt::Output(expr)              => writeln!(f, "{}output({});", pad, expr),
+            Stmt::Call(Some(r), name, args) => writeln!(f, "{}{} = {}({});", pad, r, name, args.join(", ")),
+            Stmt::Call(None, name, args)    => writeln!(f, "{}{}({});", pad, name, args.join(", ")),
+            Stmt::Return(Some(expr))        => writeln!(f, "{}return {};", pad, expr),
+            Stmt::Return(None)              => writeln!(f, "{}return;", pad),
+            Stmt::Halt                      => writeln!(f, "{}halt();", pad),
+            Stmt::Break                     => writeln!(f, "{}break;", pad),
+            Stmt::Continue                  => writeln!(f, "{}continue;", pad),
+            Stmt::Goto(target)              => writeln!(f, "{}goto L{:04};", pad, target),
+            Stmt::Computed(target)          => writeln!(f, "{}goto *{};", pad, target),
+            Stmt::Label(addr)               => writeln!(f, "{}L{:04}:", pad, addr),
+            Stmt::Raw(text)                 => writeln!(f, "{}{};", pad, text),
+            Stmt::If(cond, then, other)     => { writeln!(f, "{}if ({}) {{", pad, cond)?;
+                                                 block(f, then)?;
+                                                 if !other.is_empty() {
+                                                     writeln!(f, "{}}} else {{", pad)?;
+                                                     block(f, other)?;
+                                                 }
+                                                 writeln!(f, "{}}}", pad) },
+            Stmt::While(cond, body)         => { writeln!(f, "{}while ({}) {{", pad, cond)?;
+                                                 block(f, body)?;
+                                                 writeln!(f, "{}}}", pad) },
+            Stmt::DoWhile(body, cond)       => { writeln!(f, "{}do {{", pad)?;
+                                                 block(f, body)?;
+                                                 writeln!(f, "{}}} while ({});", pad, cond) },
+        }
+    }
+}
+
+fn negate(cond: &str) -> String {
+    match cond.strip_prefix('!') {
+        Some(rest) if SIMPLE.is_match(cond)   => rest.to_owned(),
+        None if SIMPLE.is_match(cond)         => format!("!{}", cond),
+        _                                     => format!("!({})", cond),
+    }
+}
+
+//
+// struct Signature -- what calls to a function look like
+//
+
+// The calling convention: the caller stores the arguments at [bp+1], [bp+2], ... and the return
+// address at [bp+0], then jumps. The function moves the relative base past its frame with BP n,
+// so that the return address is at [bp-n] and the arguments follow it, then the locals. It
+// returns a value in the slot of the first argument, undoes the BP and jumps to [bp+0].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Signature {
+    pub entry: usize,
+    pub frame: i128,
+    pub params: usize,
+    pub returns: bool,
+}
+
+impl Signature {
+    pub fn name(&self) -> String {
+        match self.entry {
+            0    => "main".to_owned(),
+            addr => format!("f{:04}", addr),
+        }
+    }
+
+    pub fn locals(&self) -> usize {
+        usize::try_from(self.frame - 1).unwrap_or(0).saturating_sub(self.params)
+    }
+
+    // the name of a relative operand [bp+offset]
+    fn slot(&self, offset: i128) -> String {
+        if offset >= 0 {
+            // the frame of the next call
+            return format!("s{}", offset);
+        }
+        match usize::try_from(self.frame + offset) {
+            Ok(0)                      => "ret".to_owned(),
+            Ok(i) if i <= self.params  => format!("p{}", i),
+            Ok(i)                      => format!("l{}", i - self.params),
+            Err(_)                     => format!("[bp{}]", offset),
+        }
+    }
+}
+
+//
+// struct Function
+//
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Function {
+    pub sig: Signature,
+    pub body: Vec<Stmt>,
+}
+
+impl fmt::Display for Function {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let params = (1..=self.sig.params).map(|i| format!("int p{}", i)).collect::<Vec<_>>();
+        writeln!(f, "// L{:04}, frame of {}", self.sig.entry, self.sig.frame)?;
+        writeln!(f, "{} {}({}) {{", if self.sig.returns { "int" } else { "void" }, self.sig.name(), params.join(", "))?;
+        if self.sig.locals() > 0 {
+            let locals = (1..=self.sig.locals()).map(|i| format!("l{}", i)).collect::<Vec<_>>();
+            writeln!(f, "{}int {};", " ".repeat(INDENT), locals.join(", "))?;
+        }
+        for stmt in &self.body {
+            stmt.write(f, 1)?;
+        }
+        writeln!(f, "}}")
+    }
+}
+
+//
+// struct Program -- every function found from address 0
+//
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Program {
+    pub functions: Vec<Function>,
+}
+
+impl fmt::Display for Program {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (i, function) in self.functions.iter().enumerate() {
+            if i > 0 {
+                writeln!(f)?;
+            }
+            write!(f, "{}", function)?;
+        }
+        Ok(())
+    }
+}
+
+impl From<&VM> for Program {
+    fn from(vm: &VM) -> Self {
+        // the graph decodes the VM's own opcodes, custom ones included
+        decompile(&Cfg::from(vm))
+    }
+}
+
+pub fn decompile(cfg: &Cfg) -> Program {
+    let calls = cfg.blocks
+        .values()
+        .filter_map(|block| call(cfg, block).map(|call| (block.start, call)))
+        .collect::<BTreeMap<_, _>>();
+    let mut entries = calls.values().filter_map(|call| call.target).collect::<BTreeSet<_>>();
+    entries.insert(0);
+
+    let mut sigs = BTreeMap::new();
+    let mut bodies = BTreeMap::new();
+    for &entry in &entries {
+        let frame = match cfg.blocks[&entry].instrs[0] {
+            ref instr if entry != 0 && instr.op.code == 9 && instr.modes[0] == Mode::Immediate => instr.params[0].max(0),
+            _                                                                                 => 0,
+        };
+        let blocks = reachable(cfg, &calls, &entries, entry);
+        // a function that writes the slot of its first argument returns a value in it
+        let returns = frame > 1 && blocks.iter().any(|start| cfg.blocks[start].instrs.iter().any(|instr| writes(instr) == Some(1 - frame)));
+        sigs.insert(entry, Signature { entry, frame, params: returns as usize, returns });
+        bodies.insert(entry, blocks);
+    }
+    // the arguments are what callers put in the frame of the next call
+    for (start, call) in &calls {
+        if let Some(sig) = call.target.and_then(|target| sigs.get_mut(&target)) {
+            let args = cfg.blocks[start].instrs.iter().filter_map(writes).filter(|&offset| offset > 0).max().unwrap_or(0);
+            sig.params = sig.params.max(args as usize).min(usize::try_from(sig.frame - 1).unwrap_or(0));
+        }
+    }
+
+    let functions = entries
+        .iter()
+        .map(|entry| {
+            let mut decompiler = Decompiler::new(cfg, &calls, &sigs, &sigs[entry], &bodies[entry]);
+            let end = bodies[entry].iter().map(|start| cfg.blocks[start].end()).max().unwrap_or(*entry);
+            let mut body = decompiler.region(*entry, end, usize::MAX, None);
+            // once more, now that it is known where the gotos go
+            gotos(&body, &mut decompiler.labels);
+            if !decompiler.labels.is_empty() {
+                body = decompiler.region(*entry, end, usize::MAX, None);
+            }
+            Function { sig: sigs[entry].clone(), body }
+        })
+        .collect();
+    Program { functions }
+}
+
+fn gotos(stmts: &[Stmt], targets: &mut BTreeSet<usize>) {
+    for stmt in stmts {
+        match stmt {
+            Stmt::Goto(target)          => { targets.insert(*target); },
+            Stmt::If(_, then, other)    => { gotos(then, targets);
+                                             gotos(other, targets); },
+            Stmt::While(_, body)        => gotos(body, targets),
+            Stmt::DoWhile(body, _)      => gotos(body, targets),
+            _                           => (),
+        }
+    }
+}
+
+//
+// finding functions
+//
+
+#[derive(Debug, Clone, Copy)]
+struct Call {
+    // None when the address comes from memory
+    target: Option<usize>,
+    // the instruction that pushes the return address
+    push: usize,
+    ret: usize,
+}
+
+// the offset of the relative operand the instruction writes to, if any
+fn writes(instr: &Instruction) -> Option<i128> {
+    let i = instr.op.writes?;
+    match instr.modes[i - 1] {
+        Mode::Relative => Some(instr.params[i - 1]),
+        _              => None,
+    }
+}
+
+// a block that ends by pushing a return address and jumping
+fn call(cfg: &Cfg, block: &Block) -> Option<Call> {
+    let last = block.instrs.last()?;
+    if last.op.jumps.is_none() || last.falls_through() {
+        return None;
+    }
+    let (push, ret) = block.instrs.iter().rev().skip(1).find_map(|instr| {
+        let i = instr.op.writes?;
+        if writes(instr) != Some(0) {
+            return None;
+        }
+        instr.modes[..i - 1]
+            .iter()
+            .zip(&instr.params)
+            .filter(|(&mode, _)| mode == Mode::Immediate)
+            .filter_map(|(_, &param)| usize::try_from(param).ok())
+            .find(|addr| cfg.listing.returns.contains(addr))
+            .map(|ret| (instr.addr, ret))
+    })?;
+    let target = last.target().and_then(|t| usize::try_from(t).ok()).filter(|t| cfg.blocks.contains_key(t));
+    Some(Call { target, push, ret })
+}
+
+// the blocks of a function: calls go on at their return address, and jumps stay inside
+fn reachable(cfg: &Cfg, calls: &BTreeMap<usize, Call>, entries: &BTreeSet<usize>, entry: usize) -> BTreeSet<usize> {
+    let mut seen = BTreeSet::new();
+    let mut todo = vec![entry];
+    while let Some(addr) = todo.pop() {
+        if !cfg.blocks.contains_key(&addr) || !seen.insert(addr) {
+            continue;
+        }
+        match calls.get(&addr) {
+            Some(call) => todo.push(call.ret),
+            None       => todo.extend(cfg.edges
+                              .iter()
+                              .filter(|edge| edge.from == addr && (edge.to == entry || !entries.contains(&edge.to)))
+                              .map(|edge| edge.to)),
+        }
+    }
+    seen
+}
+
+//
+// struct Decompiler -- turns the blocks of one function into statements
+//
+
+#[derive(Debug, Clone)]
+enum Exit {
+    Next(usize),
+    Stop,
+    // with the name of the function, or the address it is called through
+    Call(Call, String),
+    Return,
+    Jump(usize),
+    // jump to the address if the condition holds
+    Branch(String, usize),
+    Indirect(String),
+}
+
+struct Decompiler<'a> {
+    cfg: &'a Cfg,
+    sigs: &'a BTreeMap<usize, Signature>,
+    sig: &'a Signature,
+    blocks: BTreeMap<usize, (Vec<Stmt>, Exit)>,
+    // loop headers, with the end of the last block that jumps back to them
+    loops: BTreeMap<usize, usize>,
+    // blocks that gotos lead to
+    labels: BTreeSet<usize>,
+}
+
+impl<'a> Decompiler<'a> {
+    fn new(cfg: &'a Cfg, calls: &BTreeMap<usize, Call>, sigs: &'a BTreeMap<usize, Signature>, sig: &'a Signature, blocks: &BTreeSet<usize>) -> Self {
+        let mut decompiler = Decompiler { cfg, sigs, sig, blocks: BTreeMap::new(), loops: BTreeMap::new(), labels: BTreeSet::new() };
+        for start in blocks {
+            let block = &cfg.blocks[start];
+            let translated = decompiler.translate(block, calls.get(start));
+            if let Exit::Jump(target) | Exit::Branch(_, target) = translated.1 {
+                if target <= block.start && blocks.contains(&target) {
+                    let end = decompiler.loops.entry(target).or_insert(0);
+                    *end = block.end().max(*end);
+                }
+            }
+            decompiler.blocks.insert(*start, translated);
+        }
+        decompiler
+    }
+
+    fn operand(&self, instr: &Instruction, i: usize) -> String {
+        let param = instr.params[i - 1];
+        match instr.modes[i - 1] {
+            Mode::Immediate if instr.op.jumps == Some(i) => format!("L{:04}", param),
+            Mode::Immediate                              => param.to_string(),
+            Mode::Position                               => usize::try_from(param)
+                                                                .ok()
+                                                                .and_then(|addr| self.cfg.listing.label(addr))
+                                                                .unwrap_or_else(|| format!("mem[{}]", param)),
+            Mode::Relative                               => self.sig.slot(param),
+        }
+    }
+
+    fn binary(&self, instr: &Instruction, op: &str) -> String {
+        let (x, y) = (self.operand(instr, 1), self.operand(instr, 2));
+        let imm = |i: usize| Some(instr.params[i - 1]).filter(|_| instr.modes[i - 1] == Mode::Immediate);
+        match (op, imm(1), imm(2)) {
+            ("+", Some(0), _)               => y,
+            ("+", _, Some(0))               => x,
+            ("+", _, Some(n)) if n < 0      => format!("{} - {}", x, -n),
+            ("+", Some(n), _) if n < 0      => format!("{} - {}", y, -n),
+            ("*", Some(0), _)               => "0".to_owned(),
+            ("*", _, Some(0))               => "0".to_owned(),
+            ("*", Some(1), _)               => y,
+            ("*", _, Some(1))               => x,
+            ("*", Some(-1), _)              => format!("-{}", y),
+            ("*", _, Some(-1))              => format!("-{}", x),
+            _                               => format!("{} {} {}", x, op, y),
+        }
+    }
+
+    fn statement(&self, instr: &Instruction) -> Stmt {
+        let dst = || self.operand(instr, 3);
+        match instr.op.code {
+            1  => Stmt::Assign(dst(), self.binary(instr, "+")),
+            2  => Stmt::Assign(dst(), self.binary(instr, "*")),
+            3  => Stmt::Assign(self.operand(instr, 1), "input()".to_owned()),
+            4  => Stmt::Output(self.operand(instr, 1)),
+            7  => Stmt::Assign(dst(), self.binary(instr, "<")),
+            8  => Stmt::Assign(dst(), self.binary(instr, "==")),
+            9  => Stmt::Raw(format!("bp += {}", self.operand(instr, 1))),
+            99 => Stmt::Halt,
+            // an opcode the decompiler knows nothing about, kept as it is
+            _  => Stmt::Raw(instr.to_string()),
+        }
+    }
+
+    fn translate(&self, block: &Block, call: Option<&Call>) -> (Vec<Stmt>, Exit) {
+        let last = &block.instrs[block.instrs.len() - 1];
+        let mut skip = BTreeSet::new();
+        if block.start == self.sig.entry && self.sig.frame > 0 {
+            skip.insert(block.start);
+        }
+        let exit = match (last.op.jumps, call) {
+            (None, _) if last.falls_through()   => Exit::Next(block.end()),
+            (None, _)                           => Exit::Stop,
+            (Some(_), Some(call))               => { skip.insert(call.push);
+                                                     let name = match call.target.and_then(|target| self.sigs.get(&target)) {
+                                                         Some(sig) => sig.name(),
+                                                         None      => format!("(*{})", self.operand(last, 2)),
+                                                     };
+                                                     Exit::Call(*call, name) },
+            (Some(_), None) if last.falls_through() && last.target().is_none() => {
+                // a conditional jump to a computed address
+                let cond = self.condition(last);
+                return (self.body(block, &skip, Some(Stmt::If(cond, vec![Stmt::Computed(self.operand(last, 2))], vec![]))), Exit::Next(block.end()));
+            },
+            (Some(_), None) if last.falls_through() => match last.modes[0] {
+                // a condition that never holds
+                Mode::Immediate => Exit::Next(block.end()),
+                _               => Exit::Branch(self.condition(last), last.target().unwrap() as usize),
+            },
+            (Some(_), None)                     => match (last.target(), self.epilogue(block)) {
+                (Some(target), _)   => Exit::Jump(target as usize),
+                (None, Some(addr))  => { skip.insert(addr);
+                                         Exit::Return },
+                (None, None)        => Exit::Indirect(self.operand(last, 2)),
+            },
+        };
+        (self.body(block, &skip, None), exit)
+    }
+
+    // the statements of a block, without its final jump and the instructions in skip
+    fn body(&self, block: &Block, skip: &BTreeSet<usize>, extra: Option<Stmt>) -> Vec<Stmt> {
+        block.instrs
+            .iter()
+            .filter(|instr| instr.op.jumps.is_none() && !skip.contains(&instr.addr))
+            .map(|instr| self.statement(instr))
+            .chain(extra)
+            .collect()
+    }
+
+    // when the jump is taken
+    fn condition(&self, instr: &Instruction) -> String {
+        match instr.op.code {
+            5 => self.operand(instr, 1),
+            _ => negate(&self.operand(instr, 1)),
+        }
+    }
+
+    // the BP that undoes the prologue, when the block ends by jumping to [bp+0]
+    fn epilogue(&self, block: &Block) -> Option<usize> {
+        match &block.instrs[..] {
+            [.., bp, jump] if self.sig.frame > 0
+                && jump.modes[1] == Mode::Relative && jump.params[1] == 0
+                && bp.op.code == 9 && bp.modes[0] == Mode::Immediate && bp.params[0] == -self.sig.frame => Some(bp.addr),
+            _ => None,
+        }
+    }
+
+    // Emits the blocks in [from, to) in address order. Control that reaches the end goes on at
+    // follow; inside a loop, jumps to its header or exit become continue and break.
+    fn region(&self, from: usize, to: usize, follow: usize, inside: Option<(usize, usize)>) -> Vec<Stmt> {
+        let mut out = vec![];
+        let mut cur = from;
+        while let Some((&start, (stmts, exit))) = self.blocks.range(cur..to).next() {
+            if self.labels.contains(&start) && !(inside.map(|(header, _)| header) == Some(start) && start == from) {
+                out.push(Stmt::Label(start));
+            }
+            if let Some(&end) = self.loops.get(&start) {
+                if inside.map(|(header, _)| header) != Some(start) || start != from {
+                    let end = end.min(to);
+                    out.push(self.make_loop(self.region(start, end, end, Some((start, end)))));
+                    cur = end;
+                    continue;
+                }
+            }
+            out.extend(stmts.iter().cloned());
+            cur = self.cfg.blocks[&start].end();
+            match exit {
+                Exit::Next(next)            => cur = *next,
+                Exit::Stop                  => (),
+                Exit::Return                => out.push(Stmt::Return(Some("p1".to_owned()).filter(|_| self.sig.returns))),
+                Exit::Indirect(addr)        => out.push(Stmt::Computed(addr.clone())),
+                Exit::Call(call, name)      => { self.emit_call(&mut out, call, name);
+                                                 cur = call.ret; },
+                Exit::Jump(target)          => out.extend(self.jump(*target, cur, follow, inside)),
+                Exit::Branch(cond, target)  => {
+                    let target = *target;
+                    if let Some(stmt) = self.loop_jump(target, inside) {
+                        out.push(Stmt::If(cond.clone(), vec![stmt], vec![]));
+                        continue;
+                    }
+                    // the code up to the target is only run when the jump is not taken
+                    let end = if target > cur && target <= to { target } else if target == follow { to } else {
+                        out.push(Stmt::If(cond.clone(), vec![Stmt::Goto(target)], vec![]));
+                        continue;
+                    };
+                    let after = match self.blocks.range(cur..end).next_back() {
+                        Some((_, (_, Exit::Jump(after)))) if *after > end && *after <= to && end == target => *after,
+                        _                                                                                 => end,
+                    };
+                    let then = self.region(cur, end, if end == target { after } else { follow }, inside);
+                    let other = self.region(end, after, after, inside);
+                    out.push(match (then.is_empty(), other.is_empty()) {
+                        (true, false) => Stmt::If(cond.clone(), other, vec![]),
+                        _             => Stmt::If(negate(cond), then, other),
+                    });
+                    cur = after;
+                },
+            }
+        }
+        out
+    }
+
+    fn loop_jump(&self, target: usize, inside: Option<(usize, usize)>) -> Option<Stmt> {
+        match inside {
+            Some((header, _)) if target == header => Some(Stmt::Continue),
+            Some((_, exit)) if target == exit     => Some(Stmt::Break),
+            _                                     => None,
+        }
+    }
+
+    fn jump(&self, target: usize, next: usize, follow: usize, inside: Option<(usize, usize)>) -> Option<Stmt> {
+        if let Some(stmt) = self.loop_jump(target, inside) {
+            return Some(stmt);
+        }
+        if target == next || target == follow {
+            return None;
+        }
+        Some(Stmt::Goto(target))
+    }
+
+    // the arguments are folded into the call when they are set just before it
+    fn emit_call(&self, out: &mut Vec<Stmt>, call: &Call, name: &str) {
+        let sig = call.target.and_then(|target| self.sigs.get(&target));
+        let params = sig.map_or(0, |sig| sig.params);
+        let mut args = (1..=params).map(|i| format!("s{}", i)).collect::<Vec<_>>();
+        let mut folded = BTreeSet::new();
+        while let Some(Stmt::Assign(dst, expr)) = out.last() {
+            match dst.strip_prefix('s').and_then(|i| i.parse::<usize>().ok()) {
+                Some(i) if i >= 1 && i <= params && !TEMPORARY.is_match(expr) && folded.insert(i) => args[i - 1] = expr.clone(),
+                _                                                                                 => break,
+            }
+            out.pop();
+        }
+        let result = Some("s1".to_owned()).filter(|_| sig.is_some_and(|sig| sig.returns));
+        out.push(Stmt::Call(result, name.to_owned(), args));
+    }
+
+    // while (true) with break at the end, made into while or do-while where it fits
+    fn make_loop(&self, mut body: Vec<Stmt>) -> Stmt {
+        if !body.last().is_some_and(Stmt::ends) {
+            body.push(Stmt::Break);
+        }
+        let n = body.len();
+        match (&body[0], &body[n - 1]) {
+            (Stmt::If(cond, then, other), Stmt::Continue) if then == &[Stmt::Break] && other.is_empty() => {
+                return Stmt::While(negate(cond), body[1..n - 1].to_vec());
+            },
+            _ => (),
+        }
+        if n >= 2 {
+            if let (Stmt::If(cond, then, other), Stmt::Break) = (&body[n - 2], &body[n - 1]) {
+                if then == &[Stmt::Continue] && other.is_empty() {
+                    return Stmt::DoWhile(body[..n - 2].to_vec(), cond.clone());
+                }
+            }
+        }
+        if body[n - 1] == Stmt::Continue {
+            body.pop();
+        }
+        Stmt::While("true".to_owned(), body)
+    }
+}
+
+//
+// tests
+//
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use std::sync::Arc;
+
+    use crate::intcode::{asm, Opcode};
+    use crate::intcode::isa::{Action, InstructionSet};
+    use crate::util;
+
+    fn source(text: &str) -> String {
+        Program::from(&asm::assemble(text).unwrap()).to_string()
+    }
+
+    #[test]
+    fn test_decompile_call() {
+        let text = source("
+                    BP   100
+                    IN   [bp+1]
+                    ADD  back, 0, [bp+0]
+                    JNZ  1, double
+            back:   OUT  [bp+1]
+                    HLT
+            double: BP   2
+                    ADD  [bp-1], [bp-1], [bp-1]
+                    BP   -2
+                    JNZ  1, [bp+0]
+        ");
+        assert_eq!(text, [
+            "// L0000, frame of 0",
+            "void main() {",
+            "    bp += 100;",
+            "    s1 = f0014(input());",
+            "    output(s1);",
+            "    halt();",
+            "}",
+            "",
+            "// L0014, frame of 2",
+            "int f0014(int p1) {",
+            "    p1 = p1 + p1;",
+            "    return p1;",
+            "}",
+            ""].join("\n"));
+    }
+
+    #[test]
+    fn test_decompile_loop() {
+        let text = source("
+                    IN   [n]
+            loop:   JZ   [n], end
+                    OUT  [n]
+                    ADD  [n], -1, [n]
+                    JNZ  1, loop
+            end:    HLT
+            n:      DW   0
+        ");
+        assert_eq!(text, [
+            "// L0000, frame of 0",
+            "void main() {",
+            "    D0015 = input();",
+            "    while (D0015) {",
+            "        output(D0015);",
+            "        D0015 = D0015 - 1;",
+            "    }",
+            "    halt();",
+            "}",
+            ""].join("\n"));
+    }
+
+    #[test]
+    fn test_decompile_if_else() {
+        let text = source("
+                    IN   [x]
+                    LT   [x], 10, [x]
+                    JZ   [x], big
+                    OUT  1
+                    JNZ  1, done
+            big:    OUT  2
+            done:   HLT
+            x:      DW   0
+        ");
+        assert!(text.contains("    if (D0017) {\n        output(1);\n    } else {\n        output(2);\n    }\n    halt();\n"), "{}", text);
+    }
+
+    #[test]
+    fn test_decompile_custom() -> Result<(), Box<dyn std::error::Error>> {
+        // squares 3 into the last cell and outputs it; the decompiler keeps SQR as it is
+        static SQR: Opcode = Opcode { code: 20, name: "SQR", params: 2, writes: Some(2), jumps: None };
+        let isa = InstructionSet::standard().with(&SQR, |args| Action::Store(args[0] * args[0]))?;
+        let vm = VM::new(&[120, 3, 6, 4, 6, 99, 0]).with_isa(Arc::new(isa));
+        assert_eq!(Program::from(&vm).to_string(), [
+            "// L0000, frame of 0",
+            "void main() {",
+            "    SQR 3, [6];",
+            "    output(D0006);",
+            "    halt();",
+            "}",
+            ""].join("\n"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_decompile_puzzles() -> Result<(), Box<dyn std::error::Error>> {
+        let vm = util::get_parsed_line::<VM>("input/day19.txt")?;
+        let program = Program::from(&vm);
+        let names = program.functions.iter().map(|function| function.sig.name()).collect::<Vec<_>>();
+        assert_eq!(names, ["main", "f0225", "f0259", "f0282", "f0303"]);
+        assert_eq!(program.functions[1].sig, Signature { entry: 225, frame: 5, params: 4, returns: true });
+        for day in &[9, 13, 17, 21, 25] {
+            let vm = util::get_parsed_line::<VM>(&format!("input/day{:02}.txt", day))?;
+            assert!(Program::from(&vm).to_string().starts_with("// L0000, frame of 0\nvoid main() {\n"));
+        }
+        Ok(())
+    }
+}
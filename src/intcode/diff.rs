@@ -0,0 +1,140 @@
+use std::collections::BTreeSet;
+use std::fmt;
+
+use crate::intcode::VM;
+use crate::intcode::disasm::{Item, Listing};
+
+//
+// enum Region -- whether a cell holds an instruction, as far as the disassembler can tell
+//
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Region {
+    Code,
+    Data,
+}
+
+impl fmt::Display for Region {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Region::Code => write!(f, "code"),
+            Region::Data => write!(f, "data"),
+        }
+    }
+}
+
+//
+// struct Change -- a memory cell that holds a different value
+//
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Change {
+    pub addr: usize,
+    pub old: i128,
+    pub new: i128,
+    pub region: Region,
+}
+
+//
+// struct Diff -- what differs between two states of a VM
+//
+
+// Regions come from disassembling the old state, so a write into an instruction shows up as code.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Diff {
+    pub ip: (usize, usize),
+    pub bp: (i128, i128),
+    pub changes: Vec<Change>,
+}
+
+impl Diff {
+    pub fn new(old: &VM, new: &VM) -> Self {
+        let code = Listing::from(old).items
+            .iter()
+            .filter_map(|item| match item {
+                Item::Code(instr) => Some(instr.addr..instr.end()),
+                Item::Data(..)    => None,
+            })
+            .flatten()
+            .collect::<BTreeSet<_>>();
+
+        // only cells that either memory actually holds, so huge sparse addresses are cheap
+        let (old_dense, old_sparse) = old.memory.parts();
+        let (new_dense, new_sparse) = new.memory.parts();
+        let addrs = (0..old_dense.len().max(new_dense.len()))
+            .chain(old_sparse.map(|(addr, _)| addr))
+            .chain(new_sparse.map(|(addr, _)| addr))
+            .collect::<BTreeSet<_>>();
+
+        let changes = addrs
+            .into_iter()
+            .map(|addr| (addr, old.memory.load(addr), new.memory.load(addr)))
+            .filter(|(_, old, new)| old != new)
+            .map(|(addr, old, new)| Change { addr, old, new, region: if code.contains(&addr) { Region::Code } else { Region::Data } })
+            .collect();
+
+        Diff { ip: (old.ip, new.ip), bp: (old.bp, new.bp), changes }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.ip.0 == self.ip.1 && self.bp.0 == self.bp.1 && self.changes.is_empty()
+    }
+}
+
+impl fmt::Display for Diff {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "IP {} -> {}", self.ip.0, self.ip.1)?;
+        writeln!(f, "BP {} -> {}", self.bp.0, self.bp.1)?;
+        let in_code = self.changes.iter().filter(|c| c.region == Region::Code).count();
+        writeln!(f, "{} cells changed, {} in code", self.changes.len(), in_code)?;
+        for c in &self.changes {
+            writeln!(f, "{:6}  {:>16} {:>16}  {}", c.addr, c.old, c.new, c.region)?;
+        }
+        Ok(())
+    }
+}
+
+//
+// tests
+//
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::intcode::asm;
+
+    #[test]
+    fn test_diff() -> Result<(), Box<dyn std::error::Error>> {
+        // counts to 3 in n, then patches an ADD into a MUL before running it
+        let vm = asm::assemble("
+            loop: ADD  [n], 1, [n]
+                  LT   [n], 3, [t]
+                  JNZ  [t], loop
+                  ADD  [op], 1, [op]
+            op:   ADD  2, 3, [t]
+                  HLT
+            n:    DW   0
+            t:    DW   0
+        ")?;
+        let mut after = vm.clone();
+        after.run(&mut &[][..])?;
+
+        let diff = Diff::new(&vm, &after);
+        assert_eq!(diff.ip, (0, 19));
+        assert_eq!(diff.changes, [
+            Change { addr: 15, old: 1101, new: 1102, region: Region::Code },
+            Change { addr: 20, old: 0, new: 3, region: Region::Data },
+            Change { addr: 21, old: 0, new: 6, region: Region::Data },
+        ]);
+        assert!(diff.to_string().contains("3 cells changed, 1 in code\n"));
+        assert!(diff.to_string().contains("\n    15              1101             1102  code\n"));
+        assert!(Diff::new(&vm, &vm.clone()).is_empty());
+
+        // a write far away is a single change
+        let mut far = vm.clone();
+        *far.memory.cell_mut(1 << 40) = 5;
+        assert_eq!(Diff::new(&vm, &far).changes, [Change { addr: 1 << 40, old: 0, new: 5, region: Region::Data }]);
+        Ok(())
+    }
+}
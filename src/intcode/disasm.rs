@@ -0,0 +1,284 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::convert::TryFrom;
+use std::fmt;
+
+use crate::intcode::{Instruction, InstructionSet, Mode, VM};
+
+// column at which comments (address, cross-references) start
+const COMMENT_COLUMN: usize = 40;
+
+// maximum number of values in a single DW line
+const DATA_PER_LINE: usize = 8;
+
+//
+// enum Item -- a decoded instruction or a single data cell
+//
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Item {
+    Code(Instruction),
+    Data(usize, i128),
+}
+
+impl Item {
+    pub fn addr(&self) -> usize {
+        match self {
+            Item::Code(instr)  => instr.addr,
+            Item::Data(addr, _) => *addr,
+        }
+    }
+}
+
+//
+// struct Listing -- the disassembly of a program image
+//
+
+#[derive(Debug)]
+pub struct Listing {
+    pub items: Vec<Item>,
+    pub xrefs: BTreeMap<usize, BTreeSet<usize>>,
+    code: BTreeSet<usize>,
+    // addresses pushed as return addresses by calls
+    pub returns: BTreeSet<usize>,
+}
+
+impl Listing {
+    pub fn with_isa(memory: &[i128], isa: &InstructionSet) -> Self {
+        let (code, returns) = find_code(isa, memory);
+
+        let covered = code
+            .values()
+            .flat_map(|instr| instr.addr..instr.end())
+            .collect::<BTreeSet<usize>>();
+
+        let mut items = code.values().cloned().map(Item::Code).collect::<Vec<_>>();
+        items.extend((0..memory.len())
+            .filter(|addr| !covered.contains(addr))
+            .map(|addr| Item::Data(addr, memory[addr])));
+        items.sort_by_key(Item::addr);
+
+        let starts = items.iter().map(Item::addr).collect::<BTreeSet<usize>>();
+
+        let mut xrefs = BTreeMap::<usize, BTreeSet<usize>>::new();
+        for instr in code.values() {
+            for (i, (&mode, &param)) in instr.modes.iter().zip(&instr.params).enumerate() {
+                let referenced = match mode {
+                    Mode::Position  => true,
+                    Mode::Immediate => instr.op.jumps == Some(i + 1) || usize::try_from(param).is_ok_and(|a| returns.contains(&a)),
+                    Mode::Relative  => false,
+                };
+                match usize::try_from(param) {
+                    Ok(addr) if referenced && starts.contains(&addr) => { xrefs.entry(addr).or_default().insert(instr.addr); },
+                    _                                                => (),
+                }
+            }
+        }
+
+        Listing {
+            items,
+            xrefs,
+            code: code.keys().cloned().collect(),
+            returns,
+        }
+    }
+
+    pub fn label(&self, addr: usize) -> Option<String> {
+        if self.xrefs.contains_key(&addr) {
+            Some(format!("{}{:04}", if self.code.contains(&addr) { 'L' } else { 'D' }, addr))
+        }
+        else {
+            None
+        }
+    }
+
+    pub fn operand(&self, instr: &Instruction, i: usize) -> String {
+        let param = instr.params[i - 1];
+        let addr = usize::try_from(param).ok();
+        let label = addr.and_then(|addr| self.label(addr));
+        let code_ref = instr.op.jumps == Some(i) || addr.is_some_and(|addr| self.returns.contains(&addr));
+        match (instr.modes[i - 1], label) {
+            (Mode::Position, Some(label))              => format!("[{}]", label),
+            (Mode::Position, None)                     => format!("[{}]", param),
+            (Mode::Immediate, Some(label)) if code_ref => label,
+            (Mode::Immediate, _)                       => param.to_string(),
+            (Mode::Relative, _) if param < 0           => format!("[bp-{}]", -param),
+            (Mode::Relative, _)                        => format!("[bp+{}]", param),
+        }
+    }
+
+    pub fn text(&self, instr: &Instruction) -> String {
+        let operands = (1..=instr.params.len())
+            .map(|i| self.operand(instr, i))
+            .collect::<Vec<_>>()
+            .join(", ");
+        format!("{:<3} {}", instr.op.name, operands).trim_end().to_owned()
+    }
+
+    fn write_label(&self, f: &mut fmt::Formatter, addr: usize) -> fmt::Result {
+        if let Some(label) = self.label(addr) {
+            let refs = self.xrefs[&addr]
+                .iter()
+                .map(|a| format!("{:04}", a))
+                .collect::<Vec<_>>()
+                .join(", ");
+            writeln!(f, "{:<width$}; xref {}", format!("{}:", label), refs, width = COMMENT_COLUMN)?;
+        }
+        Ok(())
+    }
+}
+
+impl From<&VM> for Listing {
+    fn from(vm: &VM) -> Self {
+        Listing::with_isa(&vm.memory.to_vec(), &vm.isa)
+    }
+}
+
+impl fmt::Display for Listing {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut i = 0;
+        while i < self.items.len() {
+            self.write_label(f, self.items[i].addr())?;
+            match &self.items[i] {
+                Item::Code(instr) => {
+                    writeln!(f, "{:<width$}; {:04}", format!("    {}", self.text(instr)), instr.addr, width = COMMENT_COLUMN)?;
+                    i += 1;
+                },
+                Item::Data(addr, _) => {
+                    let mut values = vec![];
+                    while let Some(Item::Data(a, v)) = self.items.get(i) {
+                        if values.len() == DATA_PER_LINE || (!values.is_empty() && self.xrefs.contains_key(a)) {
+                            break;
+                        }
+                        values.push(v.to_string());
+                        i += 1;
+                    }
+                    writeln!(f, "{:<width$}; {:04}", format!("    DW  {}", values.join(", ")), addr, width = COMMENT_COLUMN)?;
+                },
+            }
+        }
+        Ok(())
+    }
+}
+
+//
+// code discovery
+//
+
+// Follows control flow from address 0 and all statically known jump targets. Calls are jumps
+// preceded by pushing an immediate return address, so immediate operands that point just past a
+// jump are treated as entry points as well, until no new code is found.
+fn find_code(isa: &InstructionSet, memory: &[i128]) -> (BTreeMap<usize, Instruction>, BTreeSet<usize>) {
+    let mut code = BTreeMap::<usize, Instruction>::new();
+    let mut covered = vec![false; memory.len()];
+    let mut tried = BTreeSet::<usize>::new();
+    let mut returns = BTreeSet::<usize>::new();
+    let mut seeds = vec![0];
+
+    while !seeds.is_empty() {
+        while let Some(addr) = seeds.pop() {
+            if !tried.insert(addr) {
+                continue;
+            }
+            let instr = match Instruction::decode_with(isa, memory, addr) {
+                Some(instr) if covered[addr..instr.end()].iter().all(|c| !c) => instr,
+                _                                                           => continue,
+            };
+            covered[addr..instr.end()].iter_mut().for_each(|c| *c = true);
+            if let Some(target) = instr.target().and_then(|t| usize::try_from(t).ok()) {
+                seeds.push(target);
+            }
+            if instr.falls_through() {
+                seeds.push(instr.end());
+            }
+            code.insert(addr, instr);
+        }
+
+        let after_jumps = code
+            .values()
+            .filter(|instr| instr.op.jumps.is_some())
+            .map(Instruction::end)
+            .collect::<BTreeSet<usize>>();
+
+        seeds = code
+            .values()
+            .flat_map(|instr| instr.modes.iter().zip(&instr.params))
+            .filter(|(&mode, _)| mode == Mode::Immediate)
+            .filter_map(|(_, &param)| usize::try_from(param).ok())
+            .filter(|addr| after_jumps.contains(addr) && !tried.contains(addr))
+            .collect();
+        returns.extend(&seeds);
+    }
+
+    returns.retain(|addr| code.contains_key(addr));
+    (code, returns)
+}
+
+//
+// tests
+//
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::util;
+
+    #[test]
+    fn test_disasm_linear() {
+        let listing = Listing::from(&VM::new(&[1002, 4, 3, 4, 33]));
+        assert_eq!(listing.to_string(), [
+            "    MUL [D0004], 3, [D0004]             ; 0000",
+            "D0004:                                  ; xref 0000",
+            "    DW  33                              ; 0004",
+            ""].join("\n"));
+    }
+
+    #[test]
+    fn test_disasm_jumps() {
+        // JZ over an OUT, then halt, followed by data
+        let listing = Listing::from(&VM::new(&[3, 9, 1006, 9, 7, 4, 9, 99, 1, 2]));
+        assert_eq!(listing.to_string(), [
+            "    IN  [D0009]                         ; 0000",
+            "    JZ  [D0009], L0007                  ; 0002",
+            "    OUT [D0009]                         ; 0005",
+            "L0007:                                  ; xref 0002",
+            "    HLT                                 ; 0007",
+            "    DW  1                               ; 0008",
+            "D0009:                                  ; xref 0000, 0002, 0005",
+            "    DW  2                               ; 0009",
+            ""].join("\n"));
+    }
+
+    #[test]
+    fn test_disasm_relative() {
+        let listing = Listing::from(&VM::new(&[109, 1, 204, -1, 99]));
+        let text = listing.items.iter().filter_map(|item| match item {
+            Item::Code(instr) => Some(listing.text(instr)),
+            _                 => None,
+        }).collect::<Vec<_>>();
+        assert_eq!(text, ["BP  1", "OUT [bp-1]", "HLT"]);
+    }
+
+    #[test]
+    fn test_disasm_call() {
+        // push return address 9, jump to subroutine at 11, which returns through [bp+0]
+        let listing = Listing::from(&VM::new(&[109, 20, 21101, 0, 9, 0, 1105, 1, 11, 99, 0, 104, 42, 2105, 1, 0]));
+        assert!(listing.code.contains(&9));
+        assert!(listing.code.contains(&11));
+        assert_eq!(listing.label(9), Some("L0009".to_owned()));
+        assert!(listing.to_string().contains("    ADD 0, L0009, [bp+0]"));
+    }
+
+    #[test]
+    fn test_disasm_day09() -> Result<(), Box<dyn std::error::Error>> {
+        let vm = util::get_parsed_line::<VM>("input/day09.txt")?;
+        let listing = Listing::from(&vm);
+        let size = listing.items.iter().map(|item| match item {
+            Item::Code(instr) => instr.size(),
+            Item::Data(_, _)  => 1,
+        }).sum::<usize>();
+        assert_eq!(size, vm.memory.len());
+        assert!(matches!(listing.items[0], Item::Code(_)));
+        Ok(())
+    }
+}
@@ -0,0 +1,308 @@
+use std::collections::{BTreeMap, HashSet};
+use std::fmt;
+
+use crate::intcode::{Instruction, Status, VM, VmError};
+use crate::intcode::disasm::{Item, Listing};
+use crate::intcode::trace::Tracer;
+use crate::intcode::watchdog::Watchdog;
+
+// instructions a single run may execute before it counts as stuck
+pub const BUDGET: u64 = 1_000_000;
+
+// values that tend to matter to puzzle programs: small numbers, newlines and printable text
+const INTERESTING: &[i128] = &[-2, -1, 0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 99, 100, 255, 1 << 31, 1 << 62];
+
+//
+// struct Rng -- xorshift64*, so that runs are reproducible from the seed
+//
+
+pub struct Rng(u64);
+
+impl Rng {
+    pub fn new(seed: u64) -> Self {
+        Rng(seed.max(1))
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+        self.0.wrapping_mul(0x2545_f491_4f6c_dd1d)
+    }
+
+    pub fn below(&mut self, n: usize) -> usize {
+        (self.next_u64() % n as u64) as usize
+    }
+
+    fn value(&mut self) -> i128 {
+        match self.below(3) {
+            0 => INTERESTING[self.below(INTERESTING.len())],
+            1 => 32 + self.below(95) as i128,
+            _ => self.next_u64() as i64 as i128,
+        }
+    }
+}
+
+//
+// enum Outcome -- how a run ended
+//
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Outcome {
+    Halted,
+    // waiting for more input than it was given, which is fine
+    Starved,
+    // including a run stopped by the watchdog, for running in circles or too long
+    Crash(VmError),
+}
+
+impl Outcome {
+    // what makes two failures the same, or None for a clean run
+    fn failure(&self) -> Option<(&'static str, usize)> {
+        match self {
+            Outcome::Halted | Outcome::Starved                      => None,
+            Outcome::Crash(VmError::UnknownOpcode { ip, .. })       => Some(("unknown opcode", *ip)),
+            Outcome::Crash(VmError::BadMode { ip, .. })             => Some(("bad mode", *ip)),
+            Outcome::Crash(VmError::NegativeAddress { ip, .. })     => Some(("negative address", *ip)),
+            Outcome::Crash(VmError::WriteToImmediate { ip, .. })    => Some(("write to immediate", *ip)),
+            Outcome::Crash(VmError::InputStarved { ip, .. })        => Some(("input starved", *ip)),
+            Outcome::Crash(VmError::Overflow { ip, .. })            => Some(("overflow", *ip)),
+            Outcome::Crash(VmError::BudgetExceeded { ip, .. })      => Some(("budget exceeded", *ip)),
+            Outcome::Crash(VmError::DeadlinePassed { ip, .. })      => Some(("deadline passed", *ip)),
+            Outcome::Crash(VmError::Cycle { ip, .. })               => Some(("infinite loop", *ip)),
+        }
+    }
+}
+
+impl fmt::Display for Outcome {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Outcome::Halted         => write!(f, "halted"),
+            Outcome::Starved        => write!(f, "waiting for input"),
+            Outcome::Crash(e)       => write!(f, "{}", e),
+        }
+    }
+}
+
+//
+// struct Coverage -- the addresses of the instructions a run executed
+//
+
+#[derive(Default)]
+pub struct Coverage(pub HashSet<usize>);
+
+impl Tracer for Coverage {
+    fn trace(&mut self, _vm: &VM, instr: &Instruction, _values: &[i128]) {
+        self.0.insert(instr.addr);
+    }
+}
+
+// runs a copy of the VM on the input until it stops, or loops, or for at most budget instructions
+pub fn execute(vm: &VM, input: &[i128], budget: u64, coverage: &mut Coverage) -> Outcome {
+    let mut vm = vm.clone();
+    let mut input = input.iter().copied();
+    let mut watchdog = Watchdog::new().with_budget(budget).detecting_cycles();
+    loop {
+        match watchdog.watch(&mut vm, |vm| vm.tick_traced(coverage, &mut || input.next(), &mut |_| ())) {
+            Err(e)                      => return Outcome::Crash(e),
+            Ok(Some(Status::Halted))    => return Outcome::Halted,
+            Ok(Some(Status::Blocked))   => return Outcome::Starved,
+            Ok(_)                       => (),
+        }
+    }
+}
+
+//
+// struct Failure -- a crash, with the first input that caused it
+//
+
+#[derive(Debug, Clone)]
+pub struct Failure {
+    pub outcome: Outcome,
+    pub input: Vec<i128>,
+    pub count: u64,
+}
+
+//
+// struct Fuzzer -- mutates inputs that reached new code, looking for crashes and hangs
+//
+
+pub struct Fuzzer {
+    vm: VM,
+    pub budget: u64,
+    rng: Rng,
+    pub runs: u64,
+    // inputs that each reached some instruction no earlier input did
+    pub corpus: Vec<Vec<i128>>,
+    pub coverage: HashSet<usize>,
+    pub failures: BTreeMap<(&'static str, usize), Failure>,
+    // instructions found by the disassembler, for reference
+    code: usize,
+}
+
+impl Fuzzer {
+    pub fn new(vm: &VM, seed: u64) -> Self {
+        let code = Listing::from(vm).items.iter().filter(|item| matches!(item, Item::Code(_))).count();
+        let mut fuzzer = Fuzzer {
+            vm: vm.clone(),
+            budget: BUDGET,
+            rng: Rng::new(seed),
+            runs: 0,
+            corpus: vec![],
+            coverage: HashSet::new(),
+            failures: BTreeMap::new(),
+            code,
+        };
+        // mutating starts from the empty input, even one that crashes before reaching any code
+        if !fuzzer.try_input(vec![]) {
+            fuzzer.corpus.push(vec![]);
+        }
+        fuzzer
+    }
+
+    // runs one input, and keeps it if it is new in some way; true if it reached new code
+    pub fn try_input(&mut self, input: Vec<i128>) -> bool {
+        let mut coverage = Coverage::default();
+        let outcome = execute(&self.vm, &input, self.budget, &mut coverage);
+        self.runs += 1;
+
+        if let Some(key) = outcome.failure() {
+            self.failures
+                .entry(key)
+                .or_insert_with(|| Failure { outcome, input: input.clone(), count: 0 })
+                .count += 1;
+        }
+        let new = coverage.0.iter().any(|addr| !self.coverage.contains(addr));
+        if new {
+            self.coverage.extend(coverage.0);
+            self.corpus.push(input);
+        }
+        new
+    }
+
+    pub fn fuzz(&mut self, runs: u64) {
+        for _ in 0..runs {
+            let input = self.mutate();
+            self.try_input(input);
+        }
+    }
+
+    fn mutate(&mut self) -> Vec<i128> {
+        let mut input = self.corpus[self.rng.below(self.corpus.len())].clone();
+        for _ in 0..=self.rng.below(4) {
+            let len = input.len();
+            match self.rng.below(5) {
+                0 if len > 0 => { let i = self.rng.below(len); input[i] = self.rng.value(); },
+                1            => { let i = self.rng.below(len + 1); let v = self.rng.value(); input.insert(i, v); },
+                2 if len > 0 => { input.remove(self.rng.below(len)); },
+                3            => { let other = self.rng.below(self.corpus.len());
+                                  let at = self.rng.below(self.corpus[other].len() + 1);
+                                  input.extend_from_slice(&self.corpus[other][at..]); },
+                _            => input.push(self.rng.value()),
+            }
+        }
+        input
+    }
+}
+
+impl fmt::Display for Fuzzer {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "runs {}, corpus {} inputs", self.runs, self.corpus.len())?;
+        writeln!(f, "instructions covered {}, disassembled {}", self.coverage.len(), self.code)?;
+        if self.failures.is_empty() {
+            return writeln!(f, "no failures");
+        }
+        writeln!(f, "failures:")?;
+        for ((kind, ip), failure) in &self.failures {
+            writeln!(f, "{:>8}x  {:04}: {}", failure.count, ip, kind)?;
+            writeln!(f, "          {}", failure.outcome)?;
+            writeln!(f, "          input {:?}", failure.input)?;
+        }
+        Ok(())
+    }
+}
+
+//
+// tests
+//
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::intcode::asm;
+    use crate::util;
+
+    #[test]
+    fn test_fuzz_crash() {
+        // only crashes after reading 3, then 5; getting there needs the corpus
+        let vm = asm::assemble("
+                  IN   [a]
+                  EQ   [a], 3, [t]
+                  JZ   [t], done
+                  IN   [a]
+                  EQ   [a], 5, [t]
+                  JZ   [t], done
+                  DW   42
+            done: HLT
+            a:    DW   0
+            t:    DW   0
+        ").unwrap();
+        let mut fuzzer = Fuzzer::new(&vm, 1);
+        fuzzer.fuzz(5000);
+        let failure = &fuzzer.failures[&("unknown opcode", 18)];
+        assert_eq!(&failure.input[..2], [3, 5]);
+        assert_eq!(failure.outcome, Outcome::Crash(VmError::UnknownOpcode { ip: 18, bp: 0, instr: 42 }));
+        assert_eq!(fuzzer.failures.len(), 1);
+        assert_eq!(fuzzer.coverage.len(), 7);
+        assert!(fuzzer.to_string().contains("x  0018: unknown opcode\n"));
+    }
+
+    #[test]
+    fn test_fuzz_hang() {
+        // reads from a negative address on 0, counts up forever on 2, and spins on anything else
+        let vm = asm::assemble("
+                   IN   [a]
+                   JNZ  [a], loop
+                   OUT  [-1]
+            loop:  EQ   [a], 2, [t]
+                   JNZ  [t], count
+            spin:  JNZ  1, spin
+            count: ADD  [n], 1, [n]
+                   JNZ  1, count
+            a:     DW   0
+            t:     DW   0
+            n:     DW   0
+        ").unwrap();
+        let mut fuzzer = Fuzzer::new(&vm, 2);
+        fuzzer.budget = 1000;
+        fuzzer.fuzz(200);
+        assert_eq!(fuzzer.failures.keys().copied().collect::<Vec<_>>(), [
+            ("budget exceeded", 17),
+            ("infinite loop", 14),
+            ("negative address", 5),
+        ]);
+        assert_eq!(execute(&vm, &[], 1000, &mut Coverage::default()), Outcome::Starved);
+    }
+
+    #[test]
+    fn test_fuzz_crash_at_start() {
+        let vm = VM::new(&[42]);
+        let mut fuzzer = Fuzzer::new(&vm, 4);
+        fuzzer.fuzz(10);
+        assert_eq!(fuzzer.corpus, [vec![]]);
+        assert!(fuzzer.coverage.is_empty());
+        assert_eq!(fuzzer.failures[&("unknown opcode", 0)].count, 11);
+    }
+
+    #[test]
+    fn test_fuzz_puzzle() -> Result<(), Box<dyn std::error::Error>> {
+        // day 5 takes the system ID, and any ID runs to the end without crashing
+        let vm = util::get_parsed_line::<VM>("input/day05.txt")?;
+        let mut fuzzer = Fuzzer::new(&vm, 3);
+        fuzzer.fuzz(200);
+        assert!(fuzzer.corpus.len() > 1);
+        assert!(fuzzer.coverage.len() > 10);
+        Ok(())
+    }
+}
@@ -0,0 +1,229 @@
+use std::convert::TryFrom;
+use std::io::Write;
+
+use crate::intcode::{Instruction, Mode, VM};
+use crate::intcode::trace::Tracer;
+
+// default number of rows; once they are full, each row covers twice as many instructions
+pub const ROWS: usize = 64;
+
+// accesses to cells past this are not counted, so that a far away cell does not widen every row
+const MAX_ADDR: usize = 1 << 16;
+
+// from no accesses to the most in any one place, for the terminal view
+const SHADES: &[u8] = b" .:-=+*#%@";
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Activity {
+    pub reads: u64,
+    pub writes: u64,
+}
+
+//
+// struct Heatmap -- memory reads and writes, by address and over time
+//
+
+// Each row holds the accesses made during a stretch of interval instructions, by address. Operand
+// accesses count; fetching the instruction itself does not.
+#[derive(Debug, Clone)]
+pub struct Heatmap {
+    // instructions per row
+    pub interval: u64,
+    pub executed: u64,
+    pub rows: Vec<Vec<Activity>>,
+    max_rows: usize,
+}
+
+impl Heatmap {
+    pub fn new(rows: usize) -> Self {
+        Heatmap { interval: 1, executed: 0, rows: vec![], max_rows: rows.max(1) }
+    }
+
+    // one past the highest address accessed
+    pub fn width(&self) -> usize {
+        self.rows.iter().map(Vec::len).max().unwrap_or(0)
+    }
+
+    fn touch(&mut self, addr: i128, write: bool) {
+        let addr = match usize::try_from(addr) {
+            Ok(addr) if addr < MAX_ADDR => addr,
+            _                           => return,
+        };
+        let row = self.rows.last_mut().expect("no row");
+        if row.len() <= addr {
+            row.resize(addr + 1, Activity::default());
+        }
+        if write { row[addr].writes += 1 } else { row[addr].reads += 1 }
+    }
+
+    // halves the rows by adding up neighbours
+    fn merge(&mut self) {
+        let rows = std::mem::take(&mut self.rows);
+        for pair in rows.chunks(2) {
+            let mut row = pair[0].clone();
+            for other in &pair[1..] {
+                if row.len() < other.len() {
+                    row.resize(other.len(), Activity::default());
+                }
+                for (a, b) in row.iter_mut().zip(other) {
+                    a.reads += b.reads;
+                    a.writes += b.writes;
+                }
+            }
+            self.rows.push(row);
+        }
+        self.interval *= 2;
+    }
+
+    // columns of ASCII shades, each one address range wide, one line per row
+    pub fn render(&self, columns: usize) -> String {
+        let width = self.width();
+        let per_column = width.div_ceil(columns.max(1)).max(1);
+        let cells = self.rows
+            .iter()
+            .map(|row| row
+                .chunks(per_column)
+                .map(|chunk| chunk.iter().map(|a| a.reads + a.writes).sum::<u64>())
+                .collect::<Vec<_>>())
+            .collect::<Vec<_>>();
+        let max = cells.iter().flatten().copied().max().unwrap_or(0);
+
+        let mut text = format!("addresses 0..{}, {} per column; instructions 0..{}, {} per row\n", width, per_column, self.executed, self.interval);
+        for (i, row) in cells.iter().enumerate() {
+            let line = (0..width.div_ceil(per_column))
+                .map(|c| SHADES[shade(row.get(c).copied().unwrap_or(0), max, SHADES.len() - 1)] as char)
+                .collect::<String>();
+            text += &format!("{:>10} |{}|\n", i as u64 * self.interval, line);
+        }
+        text
+    }
+
+    // a binary PPM image, one pixel per address and row: red for writes, green for reads
+    pub fn write_ppm<W: Write>(&self, w: &mut W) -> std::io::Result<()> {
+        let width = self.width().max(1);
+        let max_reads = self.rows.iter().flatten().map(|a| a.reads).max().unwrap_or(0);
+        let max_writes = self.rows.iter().flatten().map(|a| a.writes).max().unwrap_or(0);
+        write!(w, "P6\n{} {}\n255\n", width, self.rows.len())?;
+        for row in &self.rows {
+            let pixels = (0..width)
+                .flat_map(|addr| {
+                    let a = row.get(addr).copied().unwrap_or_default();
+                    [shade(a.writes, max_writes, 255) as u8, shade(a.reads, max_reads, 255) as u8, 0]
+                })
+                .collect::<Vec<_>>();
+            w.write_all(&pixels)?;
+        }
+        Ok(())
+    }
+}
+
+impl Default for Heatmap {
+    fn default() -> Self {
+        Heatmap::new(ROWS)
+    }
+}
+
+impl Tracer for Heatmap {
+    fn trace(&mut self, vm: &VM, instr: &Instruction, values: &[i128]) {
+        if self.executed.is_multiple_of(self.interval) {
+            if self.rows.len() == self.max_rows {
+                self.merge();
+            }
+            if self.executed.is_multiple_of(self.interval) {
+                self.rows.push(vec![]);
+            }
+        }
+        for (i, (&mode, &param)) in instr.modes.iter().zip(&instr.params).enumerate() {
+            match mode {
+                _ if instr.op.writes == Some(i + 1) => self.touch(values[i], true),
+                Mode::Position                      => self.touch(param, false),
+                // an address that overflows is the VM's error to report, not a read
+                Mode::Relative                      => if let Some(addr) = vm.bp.checked_add(param) { self.touch(addr, false) },
+                Mode::Immediate                     => (),
+            }
+        }
+        self.executed += 1;
+    }
+}
+
+// 0 for none, and up to top on a log scale, so that rarely touched cells still show
+fn shade(n: u64, max: u64, top: usize) -> usize {
+    match n {
+        0               => 0,
+        _ if n >= max   => top,
+        _               => 1 + ((top - 1) as f64 * (n as f64).ln() / (max as f64).ln()).round() as usize,
+    }
+}
+
+//
+// tests
+//
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::intcode::{asm, VmError};
+    use crate::util;
+
+    #[test]
+    fn test_heatmap_counter() -> Result<(), Box<dyn std::error::Error>> {
+        // 100 times: reads the constant in k, and writes the counter in n
+        let mut vm = asm::assemble("
+            loop: ADD  [n], [k], [n]
+                  LT   [n], 100, [t]
+                  JNZ  [t], loop
+                  HLT
+            n:    DW   0
+            k:    DW   1
+            t:    DW   0
+        ")?;
+        let mut heatmap = Heatmap::new(8);
+        vm.run_traced(&mut &[][..], &mut heatmap)?;
+        assert_eq!(heatmap.executed, 301);
+        assert_eq!(heatmap.interval, 64);
+        assert_eq!(heatmap.rows.len(), 5);
+        assert_eq!(heatmap.width(), 15);
+
+        let total = |addr: usize| heatmap.rows.iter().filter_map(|row| row.get(addr)).fold(Activity::default(), |a, b| Activity { reads: a.reads + b.reads, writes: a.writes + b.writes });
+        assert_eq!(total(12), Activity { reads: 200, writes: 100 });
+        assert_eq!(total(13), Activity { reads: 100, writes: 0 });
+        assert_eq!(total(14), Activity { reads: 100, writes: 100 });
+
+        let text = heatmap.render(5);
+        assert_eq!(text.lines().next(), Some("addresses 0..15, 3 per column; instructions 0..301, 64 per row"));
+        assert_eq!(text.lines().nth(1), Some("         0 |    @|"));
+        assert_eq!(text.lines().count(), 6);
+
+        let mut ppm = vec![];
+        heatmap.write_ppm(&mut ppm)?;
+        assert!(ppm.starts_with(b"P6\n15 5\n255\n"));
+        assert_eq!(ppm.len(), 12 + 15 * 5 * 3);
+        // n is read and written most often, and k only read
+        assert_eq!(ppm[12 + 12 * 3..12 + 13 * 3], [255, 255, 0]);
+        let k = &ppm[12 + 13 * 3..12 + 14 * 3];
+        assert!(k[0] == 0 && k[1] > 0 && k[1] < 255, "{:?}", k);
+        Ok(())
+    }
+
+    #[test]
+    fn test_heatmap_overflow() {
+        let mut vm = VM::new(&[109, 1, 204, i128::MAX, 99]);
+        let mut heatmap = Heatmap::default();
+        assert_eq!(vm.run_traced(&mut &[][..], &mut heatmap).unwrap_err(), VmError::Overflow { ip: 2, bp: 1, instr: 204 });
+        assert_eq!(heatmap.executed, 2);
+        assert_eq!(heatmap.width(), 0);
+    }
+
+    #[test]
+    fn test_heatmap_puzzle() -> Result<(), Box<dyn std::error::Error>> {
+        // the arcade game draws its first screen, and rows get merged well before it halts
+        let mut vm = util::get_parsed_line::<VM>("input/day13.txt")?;
+        let mut heatmap = Heatmap::default();
+        vm.run_traced(&mut &[][..], &mut heatmap)?;
+        assert!(heatmap.rows.len() > ROWS / 2 && heatmap.rows.len() <= ROWS);
+        assert!(heatmap.interval > 1);
+        assert!(heatmap.width() <= vm.memory.len());
+        Ok(())
+    }
+}
@@ -0,0 +1,194 @@
+use std::collections::VecDeque;
+use std::io::{Read, Write};
+use std::sync::mpsc::{Receiver, Sender};
+
+//
+// traits
+//
+
+// A source of input values; None means no input is available (yet).
+pub trait IntcodeInput<T = i128> {
+    fn read(&mut self) -> Option<T>;
+}
+
+// A sink for output values.
+pub trait IntcodeOutput<T = i128> {
+    fn write(&mut self, value: T);
+}
+
+//
+// queues
+//
+
+impl<T> IntcodeInput<T> for VecDeque<T> {
+    fn read(&mut self) -> Option<T> {
+        self.pop_front()
+    }
+}
+
+impl<T> IntcodeOutput<T> for VecDeque<T> {
+    fn write(&mut self, value: T) {
+        self.push_back(value)
+    }
+}
+
+// a slice is its own cursor: each read moves its start past the value, as in `&mut &[1, 2][..]`
+impl<T: Clone> IntcodeInput<T> for &[T] {
+    fn read(&mut self) -> Option<T> {
+        let (first, rest) = self.split_first()?;
+        *self = rest;
+        Some(first.clone())
+    }
+}
+
+impl<T> IntcodeOutput<T> for Vec<T> {
+    fn write(&mut self, value: T) {
+        self.push(value)
+    }
+}
+
+//
+// closures
+//
+
+impl<T, F: FnMut() -> Option<T>> IntcodeInput<T> for F {
+    fn read(&mut self) -> Option<T> {
+        self()
+    }
+}
+
+impl<T, F: FnMut(T)> IntcodeOutput<T> for F {
+    fn write(&mut self, value: T) {
+        self(value)
+    }
+}
+
+//
+// struct Iter -- input from any iterator
+//
+
+pub struct Iter<I>(pub I);
+
+impl<T, I: Iterator<Item=T>> IntcodeInput<T> for Iter<I> {
+    fn read(&mut self) -> Option<T> {
+        self.0.next()
+    }
+}
+
+//
+// struct Bytes -- one value per byte from a reader, or to a writer
+//
+
+pub struct Bytes<T> {
+    inner: T,
+    pub error: Option<std::io::Error>,
+}
+
+impl<T> Bytes<T> {
+    pub fn new(inner: T) -> Self {
+        Bytes { inner, error: None }
+    }
+}
+
+impl<R: Read> IntcodeInput for Bytes<R> {
+    fn read(&mut self) -> Option<i128> {
+        let mut buf = [0u8];
+        match self.inner.read_exact(&mut buf) {
+            Ok(())                                                   => Some(buf[0] as i128),
+            Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => None,
+            Err(e)                                                   => { self.error = Some(e); None },
+        }
+    }
+}
+
+impl<W: Write> IntcodeOutput for Bytes<W> {
+    fn write(&mut self, value: i128) {
+        if let Err(e) = self.inner.write_all(&[value as u8]).and_then(|_| self.inner.flush()) {
+            self.error = Some(e);
+        }
+    }
+}
+
+//
+// channels
+//
+
+// blocks until a value arrives; None once all senders are gone
+impl<T> IntcodeInput<T> for Receiver<T> {
+    fn read(&mut self) -> Option<T> {
+        self.recv().ok()
+    }
+}
+
+// values sent after the receiver is gone are dropped
+impl<T> IntcodeOutput<T> for Sender<T> {
+    fn write(&mut self, value: T) {
+        let _ = self.send(value);
+    }
+}
+
+//
+// tests
+//
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::intcode::{Status, VM};
+
+    // day 5 example: outputs whatever it gets as input
+    fn echo() -> VM {
+        VM::new(&[3, 0, 4, 0, 99])
+    }
+
+    #[test]
+    fn test_io_vecdeque() {
+        let mut input = VecDeque::from(vec![42]);
+        let mut output = VecDeque::new();
+        echo().run_with(&mut input, &mut output).unwrap();
+        assert_eq!(output, &[42]);
+    }
+
+    #[test]
+    fn test_io_closures() {
+        let mut seen = vec![];
+        let status = echo().step(&mut || Some(7), &mut |v| seen.push(v)).unwrap();
+        assert!(matches!(status, Status::Suspended));
+        assert_eq!(seen, &[7]);
+    }
+
+    #[test]
+    fn test_io_slice() {
+        // the slice is left pointing at what was not read
+        let mut input = &[1, 2, 3][..];
+        assert_eq!(echo().run(&mut input).unwrap(), &[1]);
+        assert_eq!(input, &[2, 3]);
+        assert!(matches!(echo().step(&mut &[][..], &mut vec![]), Ok(Status::Blocked)));
+    }
+
+    #[test]
+    fn test_io_iter() {
+        let output = echo().run(&mut Iter(std::iter::once(13))).unwrap();
+        assert_eq!(output, &[13]);
+    }
+
+    #[test]
+    fn test_io_bytes() {
+        let mut input = Bytes::new(&b"A"[..]);
+        let mut output = Bytes::new(vec![]);
+        echo().run_with(&mut input, &mut output).unwrap();
+        assert_eq!(output.inner, b"A");
+        assert!(matches!(echo().step(&mut Bytes::new(&b""[..]), &mut vec![]), Ok(Status::Blocked)));
+    }
+
+    #[test]
+    fn test_io_channels() {
+        let (tx_in, mut rx_in) = std::sync::mpsc::channel();
+        let (mut tx_out, rx_out) = std::sync::mpsc::channel();
+        let handle = std::thread::spawn(move || echo().run_with(&mut rx_in, &mut tx_out));
+        tx_in.send(99).unwrap();
+        assert_eq!(rx_out.recv(), Ok(99));
+        handle.join().unwrap().unwrap();
+    }
+}
@@ -0,0 +1,218 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::intcode::{Opcode, OPCODES};
+
+lazy_static! {
+    // shared by every VM that was not given more opcodes
+    pub static ref STANDARD: Arc<InstructionSet> = Arc::new(InstructionSet::standard());
+}
+
+//
+// enum Action -- what a custom instruction does once it has read its operands
+//
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    // on to the next instruction
+    Next,
+    // writes the value to the parameter the opcode writes to, then on to the next instruction
+    Store(i128),
+    Jump(i128),
+    // outputs the value, then halts
+    Exit(i128),
+}
+
+// Called with the operand values, except for the parameter the opcode writes to, which holds the
+// target address instead. Handlers are shared between clones of a VM, and between threads.
+pub type Handler = Arc<dyn Fn(&[i128]) -> Action + Send + Sync>;
+
+#[derive(Debug, PartialEq, thiserror::Error)]
+pub enum IsaError {
+    #[error("Opcode {0} must be between 0 and 98")]
+    BadCode(i128),
+    #[error("Opcode {0} is taken by {1}")]
+    CodeTaken(i128, &'static str),
+    #[error("Mnemonic {0} is taken")]
+    NameTaken(&'static str),
+    #[error("{0} has {1} parameters, but an instruction only has room for 3 modes")]
+    TooManyParams(&'static str, usize),
+    #[error("{0} writes to or jumps through parameter {1}, which it does not have")]
+    BadParam(&'static str, usize),
+}
+
+//
+// struct InstructionSet -- the standard opcodes, and any added to them
+//
+
+// Standard opcodes are executed directly; an added one is only looked up when the VM meets an
+// opcode it does not know. They are described by an Opcode, usually a static like the standard
+// ones, so that the disassembler, assembler and tracer pick them up without knowing about them.
+#[derive(Clone, Default)]
+pub struct InstructionSet {
+    custom: BTreeMap<i128, (&'static Opcode, Handler)>,
+}
+
+impl InstructionSet {
+    pub fn standard() -> Self {
+        InstructionSet::default()
+    }
+
+    pub fn with<F>(mut self, op: &'static Opcode, handler: F) -> Result<Self, IsaError>
+        where
+            F: Fn(&[i128]) -> Action + Send + Sync + 'static
+    {
+        if !(0..99).contains(&op.code) {
+            return Err(IsaError::BadCode(op.code));
+        }
+        if let Some(other) = self.by_code(op.code) {
+            return Err(IsaError::CodeTaken(op.code, other.name));
+        }
+        if self.by_name(op.name).is_some() {
+            return Err(IsaError::NameTaken(op.name));
+        }
+        if op.params > 3 {
+            return Err(IsaError::TooManyParams(op.name, op.params));
+        }
+        if let Some(&i) = op.writes.iter().chain(&op.jumps).find(|&&i| i == 0 || i > op.params) {
+            return Err(IsaError::BadParam(op.name, i));
+        }
+        self.custom.insert(op.code, (op, Arc::new(handler)));
+        Ok(self)
+    }
+
+    pub fn by_code(&self, code: i128) -> Option<&'static Opcode> {
+        Opcode::by_code(code).or_else(|| self.custom.get(&code).map(|&(op, _)| op))
+    }
+
+    pub fn by_name(&self, name: &str) -> Option<&'static Opcode> {
+        Opcode::by_name(name).or_else(|| self.added().find(|op| op.name.eq_ignore_ascii_case(name)))
+    }
+
+    // the opcodes added to the standard ones
+    pub fn added(&self) -> impl Iterator<Item = &'static Opcode> + '_ {
+        self.custom.values().map(|&(op, _)| op)
+    }
+
+    pub fn opcodes(&self) -> impl Iterator<Item = &'static Opcode> + '_ {
+        OPCODES.iter().chain(self.added())
+    }
+
+    pub(crate) fn handler(&self, code: i128) -> Option<(&'static Opcode, Handler)> {
+        self.custom.get(&code).cloned()
+    }
+}
+
+impl fmt::Debug for InstructionSet {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_list().entries(self.opcodes().map(|op| op.name)).finish()
+    }
+}
+
+// two sets are the same if they have the same opcodes; handlers cannot be compared
+impl PartialEq for InstructionSet {
+    fn eq(&self, other: &Self) -> bool {
+        self.added().eq(other.added())
+    }
+}
+
+//
+// the extended set -- for hand-written programs, not used by any puzzle
+//
+
+pub static PRT: Opcode  = Opcode { code: 10, name: "PRT",  params: 1, writes: None,    jumps: None };
+pub static EXIT: Opcode = Opcode { code: 11, name: "EXIT", params: 1, writes: None,    jumps: None };
+pub static JMP: Opcode  = Opcode { code: 12, name: "JMP",  params: 1, writes: None,    jumps: Some(1) };
+pub static TIME: Opcode = Opcode { code: 13, name: "TIME", params: 1, writes: Some(1), jumps: None };
+
+// PRT prints a value to stderr, EXIT halts with a value as the last output, JMP always jumps,
+// and TIME stores the milliseconds since the epoch
+pub fn extended() -> InstructionSet {
+    let set = InstructionSet::standard()
+        .with(&PRT,  |args| { eprintln!("{}", args[0]); Action::Next })
+        .and_then(|set| set.with(&EXIT, |args| Action::Exit(args[0])))
+        .and_then(|set| set.with(&JMP,  |args| Action::Jump(args[0])))
+        .and_then(|set| set.with(&TIME, |_| Action::Store(SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_millis() as i128))));
+    match set {
+        Ok(set) => set,
+        Err(e)  => panic!("{}", e),
+    }
+}
+
+//
+// tests
+//
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::intcode::{asm, disasm, Instruction, VM, VmError};
+    use crate::intcode::trace::Profiler;
+
+    static SQR: Opcode = Opcode { code: 20, name: "SQR", params: 2, writes: Some(2), jumps: None };
+
+    fn squares() -> Arc<InstructionSet> {
+        Arc::new(InstructionSet::standard().with(&SQR, |args| Action::Store(args[0] * args[0])).unwrap())
+    }
+
+    #[test]
+    fn test_isa_errors() {
+        static BIG: Opcode = Opcode { code: 120, name: "BIG", params: 0, writes: None, jumps: None };
+        static ADD: Opcode = Opcode { code: 21, name: "add", params: 3, writes: Some(3), jumps: None };
+        static FOUR: Opcode = Opcode { code: 22, name: "FOUR", params: 4, writes: None, jumps: None };
+        static NONE: Opcode = Opcode { code: 23, name: "NONE", params: 1, writes: Some(2), jumps: None };
+        let add = |op| InstructionSet::standard().with(op, |_| Action::Next).err();
+        assert_eq!(add(&BIG), Some(IsaError::BadCode(120)));
+        assert_eq!(add(&OPCODES[0]), Some(IsaError::CodeTaken(1, "ADD")));
+        assert_eq!(add(&ADD), Some(IsaError::NameTaken("add")));
+        assert_eq!(add(&FOUR), Some(IsaError::TooManyParams("FOUR", 4)));
+        assert_eq!(add(&NONE), Some(IsaError::BadParam("NONE", 2)));
+        assert_eq!((*squares()).clone().with(&SQR, |_| Action::Next).err(), Some(IsaError::CodeTaken(20, "SQR")));
+    }
+
+    #[test]
+    fn test_isa_execute() -> Result<(), VmError> {
+        let isa = squares();
+        // squares the 12 at 11, then 7
+        let program = [20, 11, 12, 4, 12, 120, 7, 12, 4, 12, 99, 12, 0];
+        let mut vm = VM::new(&program).with_isa(isa.clone());
+        assert_eq!(vm.run(&mut &[][..])?, [144, 49]);
+        assert_eq!(VM::new(&program).run(&mut &[][..]), Err(VmError::UnknownOpcode { ip: 0, bp: 0, instr: 20 }));
+
+        // the parameter written to cannot be immediate
+        let mut vm = VM::new(&[1120, 3, 3, 99]).with_isa(isa);
+        assert_eq!(vm.run(&mut &[][..]), Err(VmError::WriteToImmediate { ip: 0, bp: 0, instr: 1120, param: 2 }));
+
+        // JMP over a HLT to an EXIT
+        let mut vm = VM::new(&[112, 3, 99, 111, 42]).with_isa(Arc::new(extended()));
+        assert_eq!(vm.run(&mut &[][..])?, [42]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_isa_tools() {
+        let isa = squares();
+        let vm = asm::assemble_with("
+                sqr  7, [x]
+                out  [x]
+                hlt
+            x:  dw   0
+        ", isa.clone()).unwrap();
+        assert_eq!(vm.memory, &[120, 7, 6, 4, 6, 99, 0]);
+        assert_eq!(vm.isa, isa);
+
+        let instr = Instruction::decode_with(&isa, &[120, 7, 6][..], 0).unwrap();
+        assert_eq!(instr.to_string(), "SQR 7, [6]");
+        assert_eq!(Instruction::decode_with(&STANDARD, &[120, 7, 6][..], 0), None);
+        let listing = disasm::Listing::from(&vm).to_string();
+        assert!(listing.contains("    SQR 7, [D0006]"), "{}", listing);
+
+        let mut profiler = Profiler::new();
+        vm.clone().run_traced(&mut &[][..], &mut profiler).unwrap();
+        assert_eq!(profiler.opcodes["SQR"], 1);
+        assert_eq!(format!("{:?}", isa), "[\"ADD\", \"MUL\", \"IN\", \"OUT\", \"JNZ\", \"JZ\", \"LT\", \"EQ\", \"BP\", \"HLT\", \"SQR\"]");
+    }
+}
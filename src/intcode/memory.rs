@@ -0,0 +1,404 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::ops::{Index, IndexMut};
+use std::sync::Arc;
+
+use crate::intcode::code::{Code, Decoded};
+use crate::intcode::word::Word;
+
+// writes further than this past the end of the dense cells go into the sparse map instead, so
+// that a single write to a huge address does not allocate a huge vector
+const MAX_GAP: usize = 1 << 16;
+
+//
+// trait Cells -- read access to either VM memory or a plain slice
+//
+
+pub trait Cells {
+    fn cell(&self, addr: usize) -> Option<i128>;
+}
+
+impl Cells for [i128] {
+    fn cell(&self, addr: usize) -> Option<i128> {
+        self.get(addr).copied()
+    }
+}
+
+impl Cells for Memory {
+    fn cell(&self, addr: usize) -> Option<i128> {
+        if addr < self.len { Some(self.load(addr)) } else { None }
+    }
+}
+
+//
+// struct Memory -- copy-on-write memory
+//
+
+// Cells are kept in pages that clones share. A write copies just the one page it lands in, if that
+// page is still shared, so cloning a VM is cheap and so is running the clone for a little while.
+// Cells far beyond the program are kept in a map. Decoded instructions are kept here too, so that
+// every write, whoever makes it, invalidates them.
+//
+// Only writes grow memory: reading past the end reads zero, and len stays as it was.
+#[derive(Clone, Default)]
+pub struct Memory<T = i128> {
+    pages: Vec<Arc<Page<T>>>,
+    sparse: Arc<BTreeMap<usize, T>>,
+    len: usize,
+    zero: T,
+    code: Code,
+}
+
+impl<T: Word> Memory<T> {
+    // one more than the highest address written to, or the length of the program
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn get(&self, addr: usize) -> Option<&T> {
+        if addr >= self.len {
+            return None;
+        }
+        Some(self.page_cell(addr).or_else(|| self.sparse.get(&addr)).unwrap_or(&self.zero))
+    }
+
+    // reads a cell; anything never written to is zero
+    #[inline]
+    pub fn load(&self, addr: usize) -> T {
+        // cells past len are kept zeroed, so the length check can be skipped here
+        match self.page_cell(addr) {
+            Some(value) => value.clone(),
+            None        => self.load_sparse(addr),
+        }
+    }
+
+    // a mutable reference to a cell, growing memory and unsharing its page as needed
+    #[inline]
+    pub fn cell_mut(&mut self, addr: usize) -> &mut T {
+        self.code.invalidate(addr);
+        if addr >= self.len {
+            self.len = addr + 1;
+        }
+        if addr < self.dense_len() + MAX_GAP {
+            if addr >= self.dense_len() {
+                self.grow(addr / PAGE + 1);
+            }
+            &mut Arc::make_mut(&mut self.pages[addr / PAGE])[addr % PAGE]
+        }
+        else {
+            Arc::make_mut(&mut self.sparse).entry(addr).or_default()
+        }
+    }
+
+    // shrinks memory back to len cells; the cells cut off read as zero again if memory regrows
+    pub fn truncate(&mut self, len: usize) {
+        if len >= self.len {
+            return;
+        }
+        self.pages.truncate(len.div_ceil(PAGE));
+        if !len.is_multiple_of(PAGE) && len < self.dense_len() {
+            let page = Arc::make_mut(&mut self.pages[len / PAGE]);
+            page[len % PAGE..].fill(T::default());
+        }
+        if self.sparse.range(len..).next().is_some() {
+            Arc::make_mut(&mut self.sparse).split_off(&len);
+        }
+        self.code.truncate(len);
+        self.len = len;
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item=T> + '_ {
+        (0..self.len).map(move |addr| self.load(addr))
+    }
+
+    pub fn to_vec(&self) -> Vec<T> {
+        self.iter().collect()
+    }
+
+    // the paged cells up to the last one that is not zero, and the sparse ones after that
+    pub fn parts(&self) -> (Vec<T>, impl Iterator<Item=(usize, T)> + '_) {
+        let mut dense = (0..self.dense_len().min(self.len)).map(|addr| self.load(addr)).collect::<Vec<_>>();
+        while dense.last().is_some_and(|value| *value == self.zero) {
+            dense.pop();
+        }
+        (dense, self.sparse.iter().map(|(&addr, value)| (addr, value.clone())))
+    }
+
+    // None unless the parts fit together the way parts makes them: the paged cells within len, and
+    // the sparse ones after the last page, in order and also within len
+    pub fn from_parts<I: IntoIterator<Item=(usize, T)>>(len: usize, dense: Vec<T>, sparse: I) -> Option<Self> {
+        let pages = paged(&dense);
+        let mut next = pages.len() * PAGE;
+        let mut map = BTreeMap::new();
+        for (addr, value) in sparse {
+            if addr < next || addr >= len {
+                return None;
+            }
+            next = addr + 1;
+            map.insert(addr, value);
+        }
+        if dense.len() > len {
+            return None;
+        }
+        Some(Memory { len, pages, sparse: Arc::new(map), zero: T::default(), code: Code::default() })
+    }
+
+    // the instruction at addr, decoded only the first time it is executed
+    #[inline]
+    pub fn decoded(&mut self, addr: usize) -> Decoded {
+        match self.code.get(addr) {
+            Some(decoded) => decoded,
+            None          => self.decode(addr),
+        }
+    }
+
+    #[cold]
+    fn decode(&mut self, addr: usize) -> Decoded {
+        // an instruction too big for an i128 is not a valid instruction anyway
+        let decoded = Decoded::new(self.load(addr).to_i128().unwrap_or(i128::MAX));
+        let limit = self.dense_len();
+        self.code.insert(addr, decoded, limit);
+        decoded
+    }
+
+    #[cold]
+    fn load_sparse(&self, addr: usize) -> T {
+        self.sparse.get(&addr).cloned().unwrap_or_default()
+    }
+
+    #[inline]
+    fn page_cell(&self, addr: usize) -> Option<&T> {
+        self.pages.get(addr / PAGE).map(|page| &page[addr % PAGE])
+    }
+
+    // how many cells the pages hold
+    #[inline]
+    fn dense_len(&self) -> usize {
+        self.pages.len() * PAGE
+    }
+
+    // new pages are all the same zeroed page until written to, except for the cells they take over
+    // from the map
+    #[cold]
+    fn grow(&mut self, pages: usize) {
+        let zero = page(&[]);
+        self.pages.resize(pages, zero);
+        let end = self.dense_len();
+        if self.sparse.range(..end).next().is_some() {
+            let sparse = Arc::make_mut(&mut self.sparse);
+            let rest = sparse.split_off(&end);
+            for (addr, value) in std::mem::replace(sparse, rest) {
+                Arc::make_mut(&mut self.pages[addr / PAGE])[addr % PAGE] = value;
+            }
+        }
+    }
+}
+
+//
+// pages
+//
+
+const PAGE: usize = 256;
+
+type Page<T> = [T; PAGE];
+
+fn page<T: Word>(cells: &[T]) -> Arc<Page<T>> {
+    let mut page = std::array::from_fn(|_| T::default());
+    page[..cells.len()].clone_from_slice(cells);
+    Arc::new(page)
+}
+
+fn paged<T: Word>(cells: &[T]) -> Vec<Arc<Page<T>>> {
+    cells.chunks(PAGE).map(page).collect()
+}
+
+impl<T: Word> From<&[T]> for Memory<T> {
+    fn from(program: &[T]) -> Self {
+        Memory {
+            pages: paged(program),
+            sparse: Arc::default(),
+            len: program.len(),
+            zero: T::default(),
+            code: Code::default(),
+        }
+    }
+}
+
+impl<T: Word> Index<usize> for Memory<T> {
+    type Output = T;
+
+    fn index(&self, addr: usize) -> &T {
+        match self.get(addr) {
+            Some(value) => value,
+            None        => panic!("address {} out of bounds, memory size is {}", addr, self.len),
+        }
+    }
+}
+
+// like Vec, writing by index only works within bounds; the VM itself grows memory with cell_mut
+impl<T: Word> IndexMut<usize> for Memory<T> {
+    fn index_mut(&mut self, addr: usize) -> &mut T {
+        if addr >= self.len {
+            panic!("address {} out of bounds, memory size is {}", addr, self.len);
+        }
+        self.cell_mut(addr)
+    }
+}
+
+impl<T: Word> fmt::Debug for Memory<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.len <= self.dense_len() {
+            f.debug_list().entries(self.iter()).finish()
+        }
+        else {
+            f.debug_struct("Memory")
+                .field("len", &self.len)
+                .field("dense", &self.parts().0)
+                .field("sparse", &self.sparse)
+                .finish()
+        }
+    }
+}
+
+// compares cell by cell, however the cells happen to be stored
+impl<T: Word> PartialEq for Memory<T> {
+    fn eq(&self, other: &Memory<T>) -> bool {
+        self.len == other.len
+            && (0..self.dense_len().max(other.dense_len())).all(|addr| self.load(addr) == other.load(addr))
+            && self.sparse.keys().chain(other.sparse.keys()).all(|&addr| self.load(addr) == other.load(addr))
+    }
+}
+
+impl<T: Word> PartialEq<[T]> for Memory<T> {
+    fn eq(&self, other: &[T]) -> bool {
+        self.len == other.len() && self.iter().eq(other.iter().cloned())
+    }
+}
+
+impl<T: Word, const N: usize> PartialEq<[T; N]> for Memory<T> {
+    fn eq(&self, other: &[T; N]) -> bool {
+        *self == other[..]
+    }
+}
+
+impl<T: Word, const N: usize> PartialEq<&[T; N]> for Memory<T> {
+    fn eq(&self, other: &&[T; N]) -> bool {
+        *self == other[..]
+    }
+}
+
+impl<T: Word> PartialEq<Memory<T>> for Vec<T> {
+    fn eq(&self, other: &Memory<T>) -> bool {
+        *other == self[..]
+    }
+}
+
+//
+// tests
+//
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn same_page(a: &Memory, b: &Memory, page: usize) -> bool {
+        Arc::ptr_eq(&a.pages[page], &b.pages[page])
+    }
+
+    #[test]
+    fn test_memory_copy_on_write() {
+        let mut a: Memory = Memory::from(&[1, 2, 3][..]);
+        let b = a.clone();
+        assert!(same_page(&a, &b, 0));
+        a[1] = 20;
+        assert_eq!(a, &[1, 20, 3]);
+        assert_eq!(b, &[1, 2, 3]);
+        assert!(!same_page(&a, &b, 0));
+    }
+
+    #[test]
+    fn test_memory_pages() {
+        // a write copies only the page it lands in
+        let mut a: Memory = Memory::from(&(0..3 * PAGE as i128).collect::<Vec<_>>()[..]);
+        let b = a.clone();
+        a[PAGE + 1] = -1;
+        assert!(same_page(&a, &b, 0));
+        assert!(!same_page(&a, &b, 1));
+        assert!(same_page(&a, &b, 2));
+        assert_eq!(a.load(PAGE + 1), -1);
+        assert_eq!(b.load(PAGE + 1), PAGE as i128 + 1);
+    }
+
+    #[test]
+    fn test_memory_read_does_not_grow() {
+        let mut memory: Memory = Memory::from(&[1, 2, 3][..]);
+        assert_eq!(memory.load(1000), 0);
+        assert_eq!(memory.get(1000), None);
+        assert_eq!(memory.len(), 3);
+
+        // the same goes for a program reading past its end
+        let mut vm = crate::intcode::VM::new(&[1, 1000, 1000, 5, 99, 7]);
+        vm.run(&mut &[][..]).unwrap();
+        assert_eq!(vm.memory, &[1, 1000, 1000, 5, 99, 0]);
+
+        *memory.cell_mut(1000) = 4;
+        assert_eq!(memory.len(), 1001);
+    }
+
+    #[test]
+    fn test_memory_sparse() {
+        let mut memory: Memory = Memory::from(&[99][..]);
+        *memory.cell_mut(1 << 50) = 7;
+        *memory.cell_mut(100) = 8;
+        assert_eq!(memory.len(), (1 << 50) + 1);
+        assert_eq!(memory.load(1 << 50), 7);
+        assert_eq!(memory.load(100), 8);
+        assert_eq!(memory.load(1 << 40), 0);
+        assert_eq!(memory.load(1 << 60), 0);
+        assert_eq!(memory.pages.len(), 1);
+        assert_eq!(memory.sparse.len(), 1);
+        let (dense, sparse) = memory.parts();
+        assert_eq!(dense.len(), 101);
+        assert_eq!(Memory::from_parts(memory.len(), dense, sparse), Some(memory));
+    }
+
+    #[test]
+    fn test_memory_sparse_to_pages() {
+        // 70000 starts out sparse, and the pages grow over it on the way to 100000
+        let mut memory: Memory = Memory::from(&[99][..]);
+        *memory.cell_mut(70000) = 5;
+        *memory.cell_mut(60000) = 1;
+        *memory.cell_mut(100000) = 2;
+        assert_eq!((memory.load(60000), memory.load(70000), memory.load(100000)), (1, 5, 2));
+        assert!(memory.sparse.is_empty());
+        let (dense, sparse) = memory.parts();
+        assert_eq!(Memory::from_parts(memory.len(), dense, sparse), Some(memory));
+    }
+
+    #[test]
+    fn test_memory_truncate() {
+        let mut memory: Memory = Memory::from(&[1, 2, 3][..]);
+        *memory.cell_mut(5000) = 4;
+        *memory.cell_mut(1 << 40) = 5;
+        memory.truncate(2);
+        assert_eq!(memory, &[1, 2]);
+        *memory.cell_mut(5000) = 6;
+        assert_eq!(memory.load(2), 0);
+        assert_eq!(memory.load(5000), 6);
+        assert_eq!(memory.load(1 << 40), 0);
+    }
+
+    #[test]
+    fn test_memory_decoded() {
+        let mut memory: Memory = Memory::from(&[1101, 2, 3, 4][..]);
+        assert_eq!(memory.decoded(0), Decoded::new(1101));
+        memory[0] = 1002;
+        assert_eq!(memory.decoded(0), Decoded::new(1002));
+        *memory.cell_mut(1 << 40) = 99;
+        assert_eq!(memory.decoded(1 << 40), Decoded::new(99));
+    }
+}
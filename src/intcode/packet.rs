@@ -0,0 +1,416 @@
+use std::collections::VecDeque;
+use std::convert::TryFrom;
+use std::fmt;
+use std::io::Write;
+use std::str::FromStr;
+
+use crate::intcode::{VM, VmError};
+use crate::intcode::runtime::{Event, Lockstep, Topology};
+
+//
+// struct Config
+//
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Config {
+    // node i has address i, which is the first thing it reads
+    pub nodes: usize,
+    // packets to this address go to the NAT
+    pub nat: i128,
+    // what a node reads when it has no packets waiting
+    pub empty: i128,
+    // how many ticks without traffic make the network idle
+    pub idle_ticks: usize,
+    // instructions a node may run in one tick before it is stopped as stuck
+    pub budget: u64,
+}
+
+// the network from day 23
+impl Default for Config {
+    fn default() -> Self {
+        Config { nodes: 50, nat: 255, empty: -1, idle_ticks: 2, budget: 1_000_000 }
+    }
+}
+
+//
+// struct Packet
+//
+
+// One line of a packet log: "tick src dst x y". Packets from the NAT have its address as source.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Packet {
+    pub tick: u64,
+    pub src: i128,
+    pub dst: i128,
+    pub x: i128,
+    pub y: i128,
+}
+
+impl fmt::Display for Packet {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} {} {} {} {}", self.tick, self.src, self.dst, self.x, self.y)
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+#[error("Cannot parse packet {0:?}")]
+pub struct ParsePacketError(String);
+
+impl FromStr for Packet {
+    type Err = ParsePacketError;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let error = || ParsePacketError(text.to_owned());
+        let fields = text.split_whitespace().map(|f| f.parse::<i128>().map_err(|_| error())).collect::<Result<Vec<_>, _>>()?;
+        match fields[..] {
+            [tick, src, dst, x, y] if tick >= 0 => Ok(Packet { tick: tick as u64, src, dst, x, y }),
+            _                                   => Err(error()),
+        }
+    }
+}
+
+//
+// trait Nat -- what happens to packets for the NAT address, and when the network is idle
+//
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    Continue,
+    // send x and y to a node
+    Send(i128, i128, i128),
+    Stop,
+}
+
+pub trait Nat {
+    fn receive(&mut self, packet: &Packet) -> Action;
+    fn idle(&mut self) -> Action;
+}
+
+//
+// struct Stats
+//
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct NodeStats {
+    pub sent: u64,
+    pub received: u64,
+    // ticks in which the node found no packets waiting
+    pub idle_reads: u64,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Stats {
+    pub ticks: u64,
+    // times the network went idle
+    pub idle: u64,
+    pub nodes: Vec<NodeStats>,
+    pub to_nat: u64,
+    pub from_nat: u64,
+    // packets for addresses that are neither a node nor the NAT
+    pub dropped: u64,
+}
+
+impl fmt::Display for Stats {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "ticks {}, idle {}, to NAT {}, from NAT {}, dropped {}", self.ticks, self.idle, self.to_nat, self.from_nat, self.dropped)?;
+        writeln!(f, "node     sent received     idle")?;
+        for (addr, node) in self.nodes.iter().enumerate() {
+            writeln!(f, "{:>4} {:>8} {:>8} {:>8}", addr, node.sent, node.received, node.idle_reads)?;
+        }
+        Ok(())
+    }
+}
+
+//
+// struct PacketNetwork -- day 23's bus on a Lockstep network, with a NAT, a log and statistics
+//
+
+#[derive(Debug, thiserror::Error)]
+pub enum PacketError {
+    #[error("Node {0} failed: {1}")]
+    Vm(usize, VmError),
+    #[error("Cannot write packet log: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Network still idle after {0} ticks, and the NAT does nothing about it")]
+    Stalled(u64),
+}
+
+// A tick is a round of the Lockstep network: each node in turn runs until it finds its input empty
+// for the second time, having read the empty value once, or halts. Nothing depends on timing, so
+// running again gives the same log.
+pub struct PacketNetwork<'a> {
+    config: Config,
+    network: Lockstep,
+    log: Option<&'a mut dyn Write>,
+    pub stats: Stats,
+}
+
+impl<'a> PacketNetwork<'a> {
+    pub fn new(vm: &VM, config: Config) -> Self {
+        let machines = (0..config.nodes).map(|addr| (vm.clone(), vec![addr as i128])).collect();
+        PacketNetwork {
+            network: Lockstep::new(Topology::Bus { packet: 3, idle: Some(config.empty) }, machines)
+                .with_patience(config.idle_ticks)
+                .with_budget(config.budget)
+                .watched(),
+            stats: Stats { nodes: vec![NodeStats::default(); config.nodes], ..Stats::default() },
+            log: None,
+            config,
+        }
+    }
+
+    pub fn with_log(self, log: &'a mut dyn Write) -> Self {
+        PacketNetwork { log: Some(log), ..self }
+    }
+
+    // runs until the NAT says to stop, or until every node has halted; an idle network the NAT
+    // leaves alone stays idle, so that is an error
+    pub fn run<N: Nat + ?Sized>(&mut self, nat: &mut N) -> Result<(), PacketError> {
+        let result = self.run_events(nat);
+        for (id, node) in self.stats.nodes.iter_mut().enumerate() {
+            node.idle_reads = self.network.polls(id);
+        }
+        result
+    }
+
+    fn run_events<N: Nat + ?Sized>(&mut self, nat: &mut N) -> Result<(), PacketError> {
+        let mut idle = false;
+        while let Some(event) = self.network.next_event() {
+            self.stats.ticks = self.network.rounds;
+            let tick = self.stats.ticks;
+            idle = event == Event::Idle;
+            let action = match event {
+                Event::Sent(src, dst, values) => { self.stats.nodes[src].sent += 1;
+                                                   self.stats.nodes[dst].received += 1;
+                                                   self.log(&Packet { tick, src: src as i128, dst: dst as i128, x: values[0], y: values[1] })?;
+                                                   continue; },
+                Event::Output(src, values)    => { self.stats.nodes[src].sent += 1;
+                                                   let packet = Packet { tick, src: src as i128, dst: values[0], x: values[1], y: values[2] };
+                                                   self.log(&packet)?;
+                                                   if packet.dst != self.config.nat {
+                                                       self.stats.dropped += 1;
+                                                       continue;
+                                                   }
+                                                   self.stats.to_nat += 1;
+                                                   nat.receive(&packet) },
+                Event::Idle | Event::Deadlock => { self.stats.idle += 1;
+                                                   nat.idle() },
+                Event::Halted                 => continue,
+                Event::Error(id, e)           => return Err(PacketError::Vm(id, e)),
+            };
+            match action {
+                Action::Continue        => (),
+                Action::Send(dst, x, y) => self.send(Packet { tick, src: self.config.nat, dst, x, y })?,
+                Action::Stop            => return Ok(()),
+            }
+        }
+        if idle {
+            return Err(PacketError::Stalled(self.stats.ticks));
+        }
+        Ok(())
+    }
+
+    // a packet from the NAT, which cannot send to itself
+    fn send(&mut self, packet: Packet) -> Result<(), PacketError> {
+        self.stats.from_nat += 1;
+        self.log(&packet)?;
+        match usize::try_from(packet.dst).ok().filter(|&dst| dst < self.config.nodes) {
+            Some(dst) => { self.network.send(dst, &[packet.x, packet.y]);
+                           self.stats.nodes[dst].received += 1; },
+            None      => self.stats.dropped += 1,
+        }
+        Ok(())
+    }
+
+    fn log(&mut self, packet: &Packet) -> Result<(), PacketError> {
+        if let Some(log) = &mut self.log {
+            writeln!(log, "{}", packet)?;
+        }
+        Ok(())
+    }
+}
+
+//
+// struct Replay -- a NAT that does what the NAT in a log did
+//
+
+// Sends the packets the log has coming from the NAT, at the same points: right after a packet for
+// the NAT that arrived in the same tick, or else when the network is idle. Stops at the end of the
+// log. The network is deterministic, so if nothing changed, the new log equals the old one.
+pub struct Replay<'a> {
+    // packets to and from the NAT, in order
+    script: VecDeque<&'a Packet>,
+    nat: i128,
+}
+
+impl<'a> Replay<'a> {
+    pub fn new(log: &'a [Packet], nat: i128) -> Self {
+        Replay { script: log.iter().filter(|p| p.src == nat || p.dst == nat).collect(), nat }
+    }
+
+    // the next packet from the NAT, if that is what comes next
+    fn send(&mut self, tick: Option<u64>) -> Action {
+        match self.script.front().copied() {
+            None                                                                => Action::Stop,
+            Some(p) if p.src == self.nat && tick.is_none_or(|t| t == p.tick)   => { self.script.pop_front();
+                                                                                    Action::Send(p.dst, p.x, p.y) },
+            Some(_)                                                             => Action::Continue,
+        }
+    }
+}
+
+impl<'a> Nat for Replay<'a> {
+    fn receive(&mut self, packet: &Packet) -> Action {
+        if self.script.front().is_some_and(|p| p.dst == self.nat && (p.src, p.x, p.y) == (packet.src, packet.x, packet.y)) {
+            self.script.pop_front();
+        }
+        self.send(Some(packet.tick))
+    }
+
+    fn idle(&mut self) -> Action {
+        self.send(None)
+    }
+}
+
+// where a replayed log first differs from the original; None for a packet that is missing
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Divergence {
+    pub index: usize,
+    pub expected: Option<Packet>,
+    pub actual: Option<Packet>,
+}
+
+// runs the network again as the log says the NAT ran it, and compares the packets with the log
+pub fn replay(vm: &VM, config: Config, log: &[Packet]) -> Result<Option<Divergence>, PacketError> {
+    let mut replayed = vec![];
+    let mut nat = Replay::new(log, config.nat);
+    match PacketNetwork::new(vm, config).with_log(&mut replayed).run(&mut nat) {
+        // a network that diverged may well never wake up again
+        Ok(()) | Err(PacketError::Stalled(_)) => (),
+        Err(e)                                => return Err(e),
+    }
+    let replayed = String::from_utf8_lossy(&replayed)
+        .lines()
+        .map(|line| line.parse::<Packet>().expect("the network writes packets it can parse"))
+        .collect::<Vec<_>>();
+    Ok((0..log.len().max(replayed.len()))
+        .map(|i| Divergence { index: i, expected: log.get(i).copied(), actual: replayed.get(i).copied() })
+        .find(|d| d.expected != d.actual))
+}
+
+//
+// tests
+//
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::util;
+
+    // records what it gets, and stops after the network has been idle a number of times
+    struct Recorder {
+        packets: Vec<Packet>,
+        idle: usize,
+    }
+
+    impl Nat for Recorder {
+        fn receive(&mut self, packet: &Packet) -> Action {
+            self.packets.push(*packet);
+            Action::Continue
+        }
+
+        fn idle(&mut self) -> Action {
+            self.idle -= 1;
+            match (self.idle, self.packets.last()) {
+                (0, _)       => Action::Stop,
+                (_, Some(p)) => Action::Send(0, p.x, p.y),
+                (_, None)    => Action::Continue,
+            }
+        }
+    }
+
+    #[test]
+    fn test_packet_parse() {
+        let packet = Packet { tick: 3, src: 255, dst: 0, x: -1, y: 42 };
+        assert_eq!(packet.to_string().parse::<Packet>().unwrap(), packet);
+        assert!("1 2 3 4".parse::<Packet>().is_err());
+        assert!("-1 2 3 4 5".parse::<Packet>().is_err());
+    }
+
+    #[test]
+    fn test_packet_network() {
+        // node 0 sends (1, 7, 8) once; after that every node forwards whatever it gets to the NAT at 10
+        let program = "3,100,1008,100,0,101,1006,101,18,104,1,104,7,104,8,1105,1,18,\
+                       3,102,1008,102,-1,103,1005,103,18,3,104,104,10,4,102,4,104,1105,1,18";
+        let vm = program.parse::<VM>().unwrap();
+        let mut log = vec![];
+        let mut nat = Recorder { packets: vec![], idle: 2 };
+        let mut network = PacketNetwork::new(&vm, Config { nodes: 2, nat: 10, ..Config::default() }).with_log(&mut log);
+        network.run(&mut nat).unwrap();
+        let stats = network.stats.clone();
+        assert_eq!(nat.packets.iter().map(|p| (p.src, p.x, p.y)).collect::<Vec<_>>(), vec![(1, 7, 8), (0, 7, 8)]);
+        assert_eq!(stats.ticks, 6);
+        assert_eq!(stats.idle, 2);
+        assert_eq!(stats.nodes[0], NodeStats { sent: 2, received: 1, idle_reads: 6 });
+        assert_eq!(stats.nodes[1], NodeStats { sent: 1, received: 1, idle_reads: 6 });
+        assert_eq!((stats.to_nat, stats.from_nat, stats.dropped), (2, 1, 0));
+        let log = String::from_utf8(log).unwrap();
+        let packets = log.lines().map(|line| line.parse::<Packet>().unwrap()).collect::<Vec<_>>();
+        assert_eq!(packets.iter().map(|p| (p.tick, p.src, p.dst)).collect::<Vec<_>>(), vec![(1, 0, 1), (1, 1, 10), (3, 10, 0), (4, 0, 10)]);
+    }
+
+    #[test]
+    fn test_packet_deterministic() -> Result<(), Box<dyn std::error::Error>> {
+        // the same program and configuration always give the same log
+        let vm = util::get_parsed_line::<VM>("input/day23.txt")?;
+        let logs = (0..2)
+            .map(|_| {
+                let mut log = vec![];
+                PacketNetwork::new(&vm, Config::default()).with_log(&mut log).run(&mut Recorder { packets: vec![], idle: 3 })?;
+                Ok(log)
+            })
+            .collect::<Result<Vec<_>, PacketError>>()?;
+        assert!(!logs[0].is_empty());
+        assert_eq!(logs[0], logs[1]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_packet_replay() -> Result<(), Box<dyn std::error::Error>> {
+        let vm = util::get_parsed_line::<VM>("input/day23.txt")?;
+        let mut log = vec![];
+        PacketNetwork::new(&vm, Config::default()).with_log(&mut log).run(&mut Recorder { packets: vec![], idle: 3 })?;
+        let log = String::from_utf8(log)?.lines().map(|line| line.parse()).collect::<Result<Vec<Packet>, _>>()?;
+        assert!(log.iter().any(|p| p.src == 255));
+        assert_eq!(replay(&vm, Config::default(), &log)?, None);
+
+        // a log that was tampered with shows where
+        let mut tampered = log.clone();
+        tampered[5].y += 1;
+        let divergence = replay(&vm, Config::default(), &tampered)?.unwrap();
+        assert_eq!(divergence, Divergence { index: 5, expected: Some(tampered[5]), actual: Some(log[5]) });
+        Ok(())
+    }
+
+    #[test]
+    fn test_packet_stalled() {
+        // nothing ever sends anything, and the NAT has nothing to wake the network with
+        let vm = VM::new(&[3, 100, 3, 100, 1105, 1, 2]);
+        let mut network = PacketNetwork::new(&vm, Config { nodes: 2, ..Config::default() });
+        let mut nat = Recorder { packets: vec![], idle: usize::MAX };
+        assert!(matches!(network.run(&mut nat), Err(PacketError::Stalled(3))));
+    }
+
+    #[test]
+    fn test_packet_stuck() {
+        // a node that spins after reading its address never gets to wait for a packet
+        let vm = VM::new(&[3, 100, 1105, 1, 2]);
+        let mut network = PacketNetwork::new(&vm, Config { nodes: 2, budget: 1000, ..Config::default() });
+        let mut nat = Recorder { packets: vec![], idle: usize::MAX };
+        match network.run(&mut nat) {
+            Err(PacketError::Vm(0, VmError::BudgetExceeded { ip: 2, budget: 1000, .. })) => (),
+            wtf                                                                        => panic!("{:?}", wtf),
+        }
+    }
+}
@@ -0,0 +1,220 @@
+use std::collections::VecDeque;
+
+use crate::intcode::{Status, VM, VmError};
+
+//
+// struct Entry -- what one executed instruction changed
+//
+
+#[derive(Debug, Clone, PartialEq)]
+struct Entry {
+    ip: usize,
+    bp: i128,
+    len: usize,
+    write: Option<(usize, i128)>,
+    input: Option<i128>,
+    output: bool,
+}
+
+//
+// struct Undone -- what the caller must put back after stepping backwards
+//
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Undone {
+    pub ip: usize,
+    pub write: Option<usize>,
+    pub input: Option<i128>,
+    pub output: bool,
+}
+
+//
+// struct Journal -- undo log of executed instructions
+//
+
+#[derive(Debug, Clone)]
+pub struct Journal {
+    entries: VecDeque<Entry>,
+    limit: usize,
+    pub count: u64,
+}
+
+impl Journal {
+    // keeps at most limit entries; older history is forgotten
+    pub fn new(limit: usize) -> Self {
+        Journal {
+            entries: VecDeque::new(),
+            limit,
+            count: 0,
+        }
+    }
+
+    // the earliest instruction count that can still be rewound to
+    pub fn horizon(&self) -> u64 {
+        self.count - self.entries.len() as u64
+    }
+
+    fn push(&mut self, entry: Entry) {
+        if self.entries.len() == self.limit {
+            self.entries.pop_front();
+        }
+        self.entries.push_back(entry);
+        self.count += 1;
+    }
+}
+
+impl VM {
+    // like tick, but records enough in the journal to undo the instruction
+    pub fn tick_recorded<R, W>(&mut self, journal: &mut Journal, read: &mut R, write: &mut W) -> Result<Option<Status>, VmError>
+        where
+            R: FnMut() -> Option<i128>,
+            W: FnMut(i128)
+    {
+        let d = self.memory.decoded(self.ip);
+        let target = self.executing()
+            .and_then(|instr| instr.op.writes)
+            .and_then(|i| self.addr(d, i).ok());
+        let mut entry = Entry {
+            ip: self.ip,
+            bp: self.bp,
+            len: self.memory.len(),
+            write: target.map(|a| (a, self.load(a))),
+            input: None,
+            output: false,
+        };
+
+        let status = {
+            let input = &mut entry.input;
+            let output = &mut entry.output;
+            self.tick(&mut || { *input = read(); *input },
+                      &mut |v| { *output = true; write(v) })?
+        };
+
+        match status {
+            Some(Status::Blocked) => self.memory.truncate(entry.len),
+            Some(Status::Halted)  => (),
+            _                     => journal.push(entry),
+        }
+        Ok(status)
+    }
+
+    // undoes the last recorded instruction, if any
+    pub fn step_back(&mut self, journal: &mut Journal) -> Option<Undone> {
+        let entry = journal.entries.pop_back()?;
+        journal.count -= 1;
+        if let Some((addr, old)) = entry.write {
+            self.memory[addr] = old;
+        }
+        self.memory.truncate(entry.len);
+        self.ip = entry.ip;
+        self.bp = entry.bp;
+        Some(Undone {
+            ip: entry.ip,
+            write: entry.write.map(|(addr, _)| addr),
+            input: entry.input,
+            output: entry.output,
+        })
+    }
+}
+
+//
+// tests
+//
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use std::sync::Arc;
+
+    use crate::intcode::Opcode;
+    use crate::intcode::isa::{Action, InstructionSet};
+    use crate::util;
+
+    fn run_recorded(vm: &mut VM, journal: &mut Journal, input: &mut VecDeque<i128>, output: &mut Vec<i128>) {
+        while !matches!(vm.tick_recorded(journal, &mut || input.pop_front(), &mut |v| output.push(v)).unwrap(),
+                        Some(Status::Halted) | Some(Status::Blocked)) { }
+    }
+
+    #[test]
+    fn test_reverse_to_start() {
+        let program = [109, 6, 202, 1, 8, 9, 99, 23, 42, 0];
+        let mut vm = VM::new(&program);
+        let mut journal = Journal::new(100);
+        run_recorded(&mut vm, &mut journal, &mut VecDeque::new(), &mut vec![]);
+        assert_eq!(vm.memory[9], 966);
+        assert_eq!(journal.count, 2);
+        while vm.step_back(&mut journal).is_some() { }
+        assert_eq!(vm.memory, program);
+        assert_eq!((vm.ip, vm.bp), (0, 0));
+    }
+
+    #[test]
+    fn test_reverse_io() {
+        // reads into a cell past the end of the program, then echoes it
+        let mut vm = VM::new(&[3, 20, 4, 20, 99]);
+        let mut journal = Journal::new(100);
+        let mut input = VecDeque::from(vec![42]);
+        let mut output = vec![];
+        run_recorded(&mut vm, &mut journal, &mut input, &mut output);
+        assert_eq!(output, &[42]);
+
+        let undone = vm.step_back(&mut journal).unwrap();
+        assert_eq!(undone, Undone { ip: 2, write: None, input: None, output: true });
+        let undone = vm.step_back(&mut journal).unwrap();
+        assert_eq!(undone, Undone { ip: 0, write: Some(20), input: Some(42), output: false });
+        assert_eq!(vm.memory, &[3, 20, 4, 20, 99]);
+    }
+
+    #[test]
+    fn test_reverse_limit() {
+        let mut vm = VM::new(&[1101, 1, 1, 0, 1101, 2, 2, 0, 1101, 3, 3, 0, 99]);
+        let mut journal = Journal::new(2);
+        run_recorded(&mut vm, &mut journal, &mut VecDeque::new(), &mut vec![]);
+        assert_eq!((journal.count, journal.horizon()), (3, 1));
+        vm.step_back(&mut journal);
+        vm.step_back(&mut journal);
+        assert!(vm.step_back(&mut journal).is_none());
+        assert_eq!((vm.ip, vm.memory[0]), (4, 2));
+    }
+
+    #[test]
+    fn test_reverse_custom() {
+        // squares 3 into the last cell, with an opcode the standard set does not have
+        static SQR: Opcode = Opcode { code: 20, name: "SQR", params: 2, writes: Some(2), jumps: None };
+        let isa = InstructionSet::standard().with(&SQR, |args| Action::Store(args[0] * args[0])).unwrap();
+        let mut vm = VM::new(&[120, 3, 4, 99, 0]).with_isa(Arc::new(isa));
+        let mut journal = Journal::new(100);
+        run_recorded(&mut vm, &mut journal, &mut VecDeque::new(), &mut vec![]);
+        assert_eq!(vm.memory[4], 9);
+        let undone = vm.step_back(&mut journal).unwrap();
+        assert_eq!(undone, Undone { ip: 0, write: Some(4), input: None, output: false });
+        assert_eq!(vm.memory, &[120, 3, 4, 99, 0]);
+    }
+
+    #[test]
+    fn test_reverse_high_digits() {
+        // the 1 above the modes is ignored when running, so this adds [5] to itself
+        let mut vm = VM::new(&[100001, 5, 5, 5, 99, 3]);
+        let mut journal = Journal::new(100);
+        vm.tick_recorded(&mut journal, &mut || None, &mut |_| ()).unwrap();
+        assert_eq!(vm.memory[5], 6);
+        let undone = vm.step_back(&mut journal).unwrap();
+        assert_eq!(undone.write, Some(5));
+        assert_eq!(vm.memory, &[100001, 5, 5, 5, 99, 3]);
+    }
+
+    #[test]
+    fn test_reverse_day09() -> Result<(), Box<dyn std::error::Error>> {
+        let original = util::get_parsed_line::<VM>("input/day09.txt")?;
+        let mut vm = original.clone();
+        let mut journal = Journal::new(usize::MAX);
+        let mut output = vec![];
+        run_recorded(&mut vm, &mut journal, &mut VecDeque::from(vec![1]), &mut output);
+        assert_eq!(output, &[2932210790]);
+        while vm.step_back(&mut journal).is_some() { }
+        assert_eq!(vm.memory, original.memory);
+        assert_eq!((vm.ip, vm.bp), (0, 0));
+        Ok(())
+    }
+}
@@ -0,0 +1,663 @@
+use std::convert::TryFrom;
+use std::collections::VecDeque;
+use std::ops::Range;
+use std::str::FromStr;
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread::JoinHandle;
+
+use crate::intcode::{Status, VM, VmError};
+use crate::intcode::watchdog::Watchdog;
+
+// how many values may wait in a machine's input queue before whoever writes to it has to wait
+pub const CAPACITY: usize = 1024;
+
+// how many instructions a machine runs between looking whether the network was stopped
+const STOP_CHECK: usize = 1024;
+
+//
+// enum Topology -- how machines are connected
+//
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Topology {
+    // machine i feeds machine i + 1; the last one feeds the controller
+    Pipeline,
+    // like a pipeline, but the last machine feeds the first one as well
+    Ring,
+    // the first machine feeds all the others, which feed the controller
+    Broadcast,
+    // machines write packets of this many values, the first being the address of the machine the
+    // rest is for; packets for other addresses go to the controller. A machine reading from an
+    // empty queue gets the idle value, if any, once, and waits when it tries again.
+    Bus { packet: usize, idle: Option<i128> },
+}
+
+impl FromStr for Topology {
+    type Err = ParseTopologyError;
+
+    // a bus is the day 23 one: packets of an address and two values, and -1 when idle
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        match text {
+            "pipeline"  => Ok(Topology::Pipeline),
+            "ring"      => Ok(Topology::Ring),
+            "broadcast" => Ok(Topology::Broadcast),
+            "bus"       => Ok(Topology::Bus { packet: 3, idle: Some(-1) }),
+            _           => Err(ParseTopologyError(text.to_owned())),
+        }
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+#[error("Unknown topology {0:?}, expected pipeline, ring, broadcast or bus")]
+pub struct ParseTopologyError(String);
+
+impl Topology {
+    // Where a value written by machine id goes, once the machine's buffer holds a whole packet: the
+    // machines that get it, what they get, and whether the controller gets it as well.
+    fn route(&self, id: usize, n: usize, buffer: &mut Vec<i128>, value: i128) -> Option<(Range<usize>, Vec<i128>, bool)> {
+        Some(match *self {
+            Topology::Pipeline if id + 1 < n => (id + 1..id + 2, vec![value], false),
+            Topology::Ring if id + 1 < n     => (id + 1..id + 2, vec![value], false),
+            Topology::Ring                   => (0..1, vec![value], true),
+            Topology::Broadcast if id == 0   => (1..n, vec![value], false),
+            Topology::Bus { packet, .. }     => { buffer.push(value);
+                                                  if buffer.len() < packet {
+                                                      return None;
+                                                  }
+                                                  let packet = std::mem::take(buffer);
+                                                  match usize::try_from(packet[0]) {
+                                                      Ok(to) if to < n => (to..to + 1, packet[1..].to_vec(), false),
+                                                      _                => (0..0, packet, true),
+                                                  } },
+            _                                => (0..0, vec![value], true),
+        })
+    }
+
+    // what a machine reads from an empty queue, once, before it waits
+    fn idle(&self) -> Option<i128> {
+        match *self {
+            Topology::Bus { idle, .. } => idle,
+            _                          => None,
+        }
+    }
+}
+
+//
+// enum Event -- what the controller gets to see
+//
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Event {
+    // a value or, on a bus, a whole packet, sent to the controller by a machine
+    Output(usize, Vec<i128>),
+    // values one machine sent another; only a watched Lockstep network reports these
+    Sent(usize, usize, Vec<i128>),
+    // every machine still running waits for input on an idle bus
+    Idle,
+    // every machine still running waits for input or for room to write, and none will come
+    Deadlock,
+    // every machine has halted
+    Halted,
+    // a machine failed, and stopped
+    Error(usize, VmError),
+}
+
+//
+// shared state
+//
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Activity {
+    Running,
+    Polled,
+    WaitingInput,
+    WaitingSpace,
+    Halted,
+}
+
+struct State {
+    queues: Vec<VecDeque<i128>>,
+    activity: Vec<Activity>,
+    packets: Vec<Vec<i128>>,
+    events: VecDeque<Event>,
+    // set once the network has reported being stuck or halted, until a machine resumes
+    reported: bool,
+}
+
+// A single lock guards all queues, so whether every machine is waiting can be decided exactly.
+struct Shared {
+    topology: Topology,
+    capacity: usize,
+    state: Mutex<State>,
+    wake: Vec<Condvar>,
+    events: Condvar,
+    // only ever set with the lock held, so that a machine about to wait cannot miss it
+    stopped: AtomicBool,
+}
+
+impl Shared {
+    fn lock(&self) -> MutexGuard<'_, State> {
+        self.state.lock().unwrap()
+    }
+
+    // None means the network was stopped
+    fn read(&self, id: usize) -> Option<i128> {
+        let mut state = self.lock();
+        loop {
+            if self.is_stopped() {
+                return None;
+            }
+            if let Some(value) = state.queues[id].pop_front() {
+                state.activity[id] = Activity::Running;
+                self.wake_writers(&mut state);
+                return Some(value);
+            }
+            match self.topology.idle() {
+                Some(idle) if state.activity[id] == Activity::Running => {
+                    state.activity[id] = Activity::Polled;
+                    return Some(idle);
+                },
+                _ => {
+                    state.activity[id] = Activity::WaitingInput;
+                    self.check(&mut state);
+                    state = self.wake[id].wait(state).unwrap();
+                },
+            }
+        }
+    }
+
+    fn write(&self, id: usize, value: i128) {
+        let mut state = self.lock();
+        state.activity[id] = Activity::Running;
+        let n = state.queues.len();
+        let (targets, values, emit) = match self.topology.route(id, n, &mut state.packets[id], value) {
+            Some(route) => route,
+            None        => return,
+        };
+        for to in targets {
+            state = self.deliver(state, id, to, &values);
+        }
+        if emit {
+            self.emit(&mut state, Event::Output(id, values));
+        }
+    }
+
+    // queues values for a machine, waiting for room first; a packet bigger than the capacity still
+    // fits into an empty queue
+    fn deliver<'a>(&'a self, mut state: MutexGuard<'a, State>, from: usize, to: usize, values: &[i128]) -> MutexGuard<'a, State> {
+        while !state.queues[to].is_empty() && state.queues[to].len() + values.len() > self.capacity {
+            if self.is_stopped() {
+                return state;
+            }
+            state.activity[from] = Activity::WaitingSpace;
+            self.check(&mut state);
+            state = self.wake[from].wait(state).unwrap();
+        }
+        state.activity[from] = Activity::Running;
+        state.queues[to].extend(values);
+        self.resume(&mut state, to, Activity::WaitingInput);
+        state
+    }
+
+    fn wake_writers(&self, state: &mut State) {
+        for id in 0..state.activity.len() {
+            self.resume(state, id, Activity::WaitingSpace);
+        }
+    }
+
+    fn resume(&self, state: &mut State, id: usize, waiting: Activity) {
+        if state.activity[id] == waiting {
+            state.activity[id] = Activity::Running;
+            state.reported = false;
+            self.wake[id].notify_one();
+        }
+    }
+
+    fn halt(&self, id: usize, error: Option<VmError>) {
+        let mut state = self.lock();
+        state.activity[id] = Activity::Halted;
+        if let Some(error) = error {
+            self.emit(&mut state, Event::Error(id, error));
+        }
+        self.check(&mut state);
+    }
+
+    // reports it when no machine can make progress anymore
+    fn check(&self, state: &mut State) {
+        if state.reported {
+            return;
+        }
+        let mut live = state.activity.iter().filter(|&&a| a != Activity::Halted).peekable();
+        let event = if live.peek().is_none() {
+            Event::Halted
+        }
+        else if live.clone().all(|&a| a == Activity::WaitingInput) && self.topology.idle().is_some() {
+            Event::Idle
+        }
+        else if live.all(|&a| a == Activity::WaitingInput || a == Activity::WaitingSpace) {
+            Event::Deadlock
+        }
+        else {
+            return;
+        };
+        state.reported = true;
+        self.emit(state, event);
+    }
+
+    fn is_stopped(&self) -> bool {
+        self.stopped.load(Ordering::Relaxed)
+    }
+
+    fn emit(&self, state: &mut State, event: Event) {
+        state.events.push_back(event);
+        self.events.notify_one();
+    }
+}
+
+//
+// struct Network -- VMs running on their own threads, connected by bounded queues
+//
+
+pub struct Network {
+    shared: Arc<Shared>,
+    threads: Vec<JoinHandle<()>>,
+}
+
+impl Network {
+    // starts every VM on its own thread, each with some input already queued
+    pub fn start(topology: Topology, machines: Vec<(VM, Vec<i128>)>) -> Self {
+        Network::with_capacity(topology, machines, CAPACITY)
+    }
+
+    pub fn with_capacity(topology: Topology, machines: Vec<(VM, Vec<i128>)>, capacity: usize) -> Self {
+        let n = machines.len();
+        let shared = Arc::new(Shared {
+            topology,
+            capacity,
+            state: Mutex::new(State {
+                queues: machines.iter().map(|(_, input)| input.iter().copied().collect()).collect(),
+                activity: vec![Activity::Running; n],
+                packets: vec![vec![]; n],
+                events: VecDeque::new(),
+                reported: false,
+            }),
+            wake: (0..n).map(|_| Condvar::new()).collect(),
+            events: Condvar::new(),
+            stopped: AtomicBool::new(false),
+        });
+        // without any machines, no thread would ever report that they all halted
+        shared.check(&mut shared.lock());
+        let threads = machines
+            .into_iter()
+            .enumerate()
+            .map(|(id, (vm, _))| {
+                let shared = shared.clone();
+                std::thread::spawn(move || run(vm, id, &shared))
+            })
+            .collect();
+        Network { shared, threads }
+    }
+
+    // queues input for a machine; the controller never has to wait for room
+    pub fn send(&self, id: usize, values: &[i128]) {
+        let mut state = self.shared.lock();
+        state.queues[id].extend(values);
+        self.shared.resume(&mut state, id, Activity::WaitingInput);
+    }
+
+    // waits for the next event; None once the network has nothing more to say without new input
+    pub fn next_event(&self) -> Option<Event> {
+        let mut state = self.shared.lock();
+        loop {
+            if let Some(event) = state.events.pop_front() {
+                return Some(event);
+            }
+            if state.reported {
+                return None;
+            }
+            state = self.shared.events.wait(state).unwrap();
+        }
+    }
+}
+
+// Machines stop at their next read or write, or after a few more instructions if they loop without
+// doing either, so dropping the network waits only briefly for their threads to finish.
+impl Drop for Network {
+    fn drop(&mut self) {
+        {
+            let _state = self.shared.lock();
+            self.shared.stopped.store(true, Ordering::Relaxed);
+        }
+        self.shared.wake.iter().for_each(|wake| wake.notify_all());
+        self.threads.drain(..).for_each(|thread| thread.join().unwrap());
+    }
+}
+
+// the thread of a machine
+fn run(mut vm: VM, id: usize, shared: &Shared) {
+    let mut read = || shared.read(id);
+    let mut write = |v| shared.write(id, v);
+    while !shared.is_stopped() {
+        for _ in 0..STOP_CHECK {
+            match vm.tick(&mut read, &mut write) {
+                Ok(None) | Ok(Some(Status::Suspended)) => (),
+                Ok(Some(Status::Halted))               => return shared.halt(id, None),
+                Ok(Some(Status::Blocked))              => return,
+                Err(e)                                 => return shared.halt(id, Some(e)),
+            }
+        }
+    }
+}
+
+//
+// struct Lockstep -- the same networks, run in turns on the calling thread
+//
+
+// Every round, each machine in turn runs until it waits for input or halts, so nothing depends on
+// timing, and the same input always gives the same events. Values go into the queue of the machine
+// they are for right away, and queues have no limit. After a round in which no machine read or
+// wrote anything but the idle value, the network is idle, or deadlocked if there is no idle value.
+// A machine that never waits would keep its turn forever, so a budget can stop it with an error.
+pub struct Lockstep {
+    topology: Topology,
+    machines: Vec<Machine>,
+    events: VecDeque<Event>,
+    // how many quiet rounds in a row it takes to be idle or deadlocked
+    patience: usize,
+    quiet: usize,
+    // instructions a machine may run in one turn
+    budget: Option<u64>,
+    watched: bool,
+    reported: bool,
+    pub rounds: u64,
+}
+
+struct Machine {
+    vm: VM,
+    queue: VecDeque<i128>,
+    packet: Vec<i128>,
+    halted: bool,
+    // rounds in which the machine read the idle value
+    polls: u64,
+}
+
+impl Lockstep {
+    pub fn new(topology: Topology, machines: Vec<(VM, Vec<i128>)>) -> Self {
+        Lockstep {
+            topology,
+            machines: machines
+                .into_iter()
+                .map(|(vm, input)| Machine { vm, queue: input.into(), packet: vec![], halted: false, polls: 0 })
+                .collect(),
+            events: VecDeque::new(),
+            patience: 1,
+            quiet: 0,
+            budget: None,
+            watched: false,
+            reported: false,
+            rounds: 0,
+        }
+    }
+
+    pub fn with_patience(self, rounds: usize) -> Self {
+        Lockstep { patience: rounds.max(1), ..self }
+    }
+
+    pub fn with_budget(self, budget: u64) -> Self {
+        Lockstep { budget: Some(budget), ..self }
+    }
+
+    // reports the values machines send each other as well
+    pub fn watched(self) -> Self {
+        Lockstep { watched: true, ..self }
+    }
+
+    pub fn send(&mut self, id: usize, values: &[i128]) {
+        let machine = &mut self.machines[id];
+        machine.queue.extend(values);
+        if !machine.halted {
+            self.reported = false;
+            self.quiet = 0;
+        }
+    }
+
+    // runs rounds until there is something to report; None once the network has nothing more to
+    // say without new input
+    pub fn next_event(&mut self) -> Option<Event> {
+        loop {
+            if let Some(event) = self.events.pop_front() {
+                return Some(event);
+            }
+            if self.reported {
+                return None;
+            }
+            self.round();
+        }
+    }
+
+    pub fn vm(&self, id: usize) -> &VM {
+        &self.machines[id].vm
+    }
+
+    pub fn is_halted(&self, id: usize) -> bool {
+        self.machines[id].halted
+    }
+
+    pub fn polls(&self, id: usize) -> u64 {
+        self.machines[id].polls
+    }
+
+    fn round(&mut self) {
+        self.rounds += 1;
+        let mut busy = false;
+        for id in 0..self.machines.len() {
+            busy |= self.turn(id);
+        }
+        let event = if self.machines.iter().all(|machine| machine.halted) {
+            Event::Halted
+        }
+        else if busy {
+            self.quiet = 0;
+            return;
+        }
+        else {
+            self.quiet += 1;
+            if self.quiet < self.patience {
+                return;
+            }
+            if self.topology.idle().is_some() { Event::Idle } else { Event::Deadlock }
+        };
+        self.quiet = 0;
+        self.reported = true;
+        self.events.push_back(event);
+    }
+
+    // runs a machine until it waits for input or halts; true if it did more than poll
+    fn turn(&mut self, id: usize) -> bool {
+        if self.machines[id].halted {
+            return false;
+        }
+        let idle = self.topology.idle();
+        let mut busy = false;
+        let mut polled = false;
+        let mut watchdog = Watchdog::new();
+        watchdog.budget = self.budget;
+        while !self.machines[id].halted {
+            let Machine { vm, queue, .. } = &mut self.machines[id];
+            let mut read = || match queue.pop_front() {
+                Some(value)                        => { busy = true; Some(value) },
+                None if !polled && idle.is_some() => { polled = true; idle },
+                None                               => None,
+            };
+            let mut output = vec![];
+            match vm.tick_watched(&mut watchdog, &mut read, &mut |value| output.push(value)) {
+                Ok(None) | Ok(Some(Status::Suspended)) => (),
+                Ok(Some(Status::Blocked))              => break,
+                Ok(Some(Status::Halted))               => self.machines[id].halted = true,
+                Err(e)                                 => { self.machines[id].halted = true;
+                                                            self.events.push_back(Event::Error(id, e)); },
+            }
+            for value in output {
+                busy = true;
+                self.write(id, value);
+            }
+        }
+        if polled {
+            self.machines[id].polls += 1;
+        }
+        busy || self.machines[id].halted
+    }
+
+    fn write(&mut self, id: usize, value: i128) {
+        let n = self.machines.len();
+        if let Some((targets, values, emit)) = self.topology.route(id, n, &mut self.machines[id].packet, value) {
+            for to in targets {
+                self.machines[to].queue.extend(&values);
+                if self.watched {
+                    self.events.push_back(Event::Sent(id, to, values.clone()));
+                }
+            }
+            if emit {
+                self.events.push_back(Event::Output(id, values));
+            }
+        }
+    }
+}
+
+//
+// tests
+//
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    // reads two numbers, outputs their sum, and halts
+    fn adder() -> VM {
+        VM::new(&[3, 11, 3, 12, 1, 11, 12, 13, 4, 13, 99, 0, 0, 0])
+    }
+
+    fn events(network: &Network) -> Vec<Event> {
+        std::iter::from_fn(|| network.next_event()).collect()
+    }
+
+    #[test]
+    fn test_runtime_pipeline() {
+        let network = Network::start(Topology::Pipeline, vec![(adder(), vec![1, 2]), (adder(), vec![10])]);
+        assert_eq!(events(&network), vec![Event::Output(1, vec![13]), Event::Halted]);
+    }
+
+    #[test]
+    fn test_runtime_broadcast() {
+        let echo = VM::new(&[3, 0, 4, 0, 99]);
+        let network = Network::start(Topology::Broadcast, vec![(echo, vec![7]), (adder(), vec![1]), (adder(), vec![2])]);
+        let mut events = events(&network);
+        events.sort_by_key(|e| format!("{:?}", e));
+        assert_eq!(events, vec![Event::Halted, Event::Output(1, vec![8]), Event::Output(2, vec![9])]);
+    }
+
+    #[test]
+    fn test_runtime_deadlock() {
+        // both machines want a second number before either writes anything
+        let network = Network::start(Topology::Ring, vec![(adder(), vec![1]), (adder(), vec![2])]);
+        assert_eq!(events(&network), vec![Event::Deadlock]);
+        network.send(0, &[3]);
+        assert_eq!(events(&network), vec![Event::Output(1, vec![6]), Event::Halted]);
+    }
+
+    #[test]
+    fn test_runtime_bounded() {
+        // the first machine writes forever, the second reads once and halts
+        let writer = VM::new(&[104, 1, 1105, 1, 0]);
+        let network = Network::with_capacity(Topology::Pipeline, vec![(writer, vec![]), (VM::new(&[3, 0, 99]), vec![])], 4);
+        assert_eq!(events(&network), vec![Event::Deadlock]);
+        assert_eq!(network.shared.lock().queues[1].len(), 4);
+    }
+
+    #[test]
+    fn test_runtime_bus() {
+        // the first machine sends (1, 42, 0) and idles; the second forwards what it gets to address 99
+        let sender = VM::new(&[104, 1, 104, 42, 104, 0, 3, 100, 1105, 1, 6]);
+        let forwarder = VM::new(&[3, 100, 1008, 100, -1, 102, 1005, 102, 0, 3, 101, 104, 99, 4, 100, 4, 101, 1105, 1, 0]);
+        let network = Network::start(Topology::Bus { packet: 3, idle: Some(-1) }, vec![(sender, vec![]), (forwarder, vec![])]);
+        assert_eq!(network.next_event(), Some(Event::Output(1, vec![99, 42, 0])));
+        assert_eq!(network.next_event(), Some(Event::Idle));
+        network.send(1, &[5, 6]);
+        assert_eq!(network.next_event(), Some(Event::Output(1, vec![99, 5, 6])));
+        assert_eq!(network.next_event(), Some(Event::Idle));
+    }
+
+    #[test]
+    fn test_runtime_empty() {
+        let network = Network::start(Topology::Ring, vec![]);
+        assert_eq!(events(&network), vec![Event::Halted]);
+    }
+
+    #[test]
+    fn test_runtime_drop_busy() {
+        // a machine looping without reading or writing still stops when the network is dropped
+        let network = Network::start(Topology::Pipeline, vec![(VM::new(&[1105, 1, 0]), vec![])]);
+        drop(network);
+    }
+
+    #[test]
+    fn test_runtime_error() {
+        let network = Network::start(Topology::Pipeline, vec![(VM::new(&[42]), vec![])]);
+        assert_eq!(events(&network), vec![Event::Error(0, VmError::UnknownOpcode { ip: 0, bp: 0, instr: 42 }), Event::Halted]);
+    }
+
+    fn lockstep_events(network: &mut Lockstep) -> Vec<Event> {
+        std::iter::from_fn(|| network.next_event()).collect()
+    }
+
+    #[test]
+    fn test_lockstep_pipeline() {
+        let mut network = Lockstep::new(Topology::Pipeline, vec![(adder(), vec![1, 2]), (adder(), vec![10])]);
+        assert_eq!(lockstep_events(&mut network), vec![Event::Output(1, vec![13]), Event::Halted]);
+        assert_eq!(network.rounds, 1);
+    }
+
+    #[test]
+    fn test_lockstep_deadlock() {
+        let mut network = Lockstep::new(Topology::Ring, vec![(adder(), vec![1]), (adder(), vec![2])]);
+        assert_eq!(lockstep_events(&mut network), vec![Event::Deadlock]);
+        assert!(!network.is_halted(0));
+        assert_eq!(network.vm(0).ip, 2);
+        network.send(0, &[3]);
+        assert_eq!(lockstep_events(&mut network), vec![Event::Output(1, vec![6]), Event::Halted]);
+    }
+
+    #[test]
+    fn test_lockstep_bus() {
+        // the same machines as on the threaded bus, watched
+        let sender = VM::new(&[104, 1, 104, 42, 104, 0, 3, 100, 1105, 1, 6]);
+        let forwarder = VM::new(&[3, 100, 1008, 100, -1, 102, 1005, 102, 0, 3, 101, 104, 99, 4, 100, 4, 101, 1105, 1, 0]);
+        let mut network = Lockstep::new(Topology::Bus { packet: 3, idle: Some(-1) }, vec![(sender, vec![]), (forwarder, vec![])]).watched();
+        assert_eq!(lockstep_events(&mut network), vec![Event::Sent(0, 1, vec![42, 0]), Event::Output(1, vec![99, 42, 0]), Event::Idle]);
+        assert_eq!((network.rounds, network.polls(0), network.polls(1)), (2, 2, 2));
+        network.send(1, &[5, 6]);
+        assert_eq!(lockstep_events(&mut network), vec![Event::Output(1, vec![99, 5, 6]), Event::Idle]);
+    }
+
+    #[test]
+    fn test_lockstep_error() {
+        let mut network = Lockstep::new(Topology::Pipeline, vec![(VM::new(&[42]), vec![])]);
+        assert_eq!(lockstep_events(&mut network), vec![Event::Error(0, VmError::UnknownOpcode { ip: 0, bp: 0, instr: 42 }), Event::Halted]);
+        let mut network = Lockstep::new(Topology::Ring, vec![]);
+        assert_eq!(lockstep_events(&mut network), vec![Event::Halted]);
+    }
+
+    #[test]
+    fn test_lockstep_budget() {
+        // the first machine spins without ever waiting, and is stopped; the second still runs
+        let spinner = VM::new(&[1105, 1, 0]);
+        let mut network = Lockstep::new(Topology::Pipeline, vec![(spinner, vec![]), (adder(), vec![1, 2])]).with_budget(100);
+        assert_eq!(lockstep_events(&mut network), vec![
+            Event::Error(0, VmError::BudgetExceeded { ip: 0, bp: 0, instr: 1105, budget: 100 }),
+            Event::Output(1, vec![3]),
+            Event::Halted,
+        ]);
+        assert_eq!(network.rounds, 1);
+    }
+}
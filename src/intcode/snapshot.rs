@@ -0,0 +1,268 @@
+use std::convert::TryFrom;
+use std::io::{Read, Write};
+
+use crate::intcode::{isa, Memory, VM};
+
+// File layout: magic, version byte, then LEB128 varints. IP, addresses and lengths are unsigned; BP
+// and all values are zigzag-encoded so small negative numbers stay small.
+//
+//     version 1: "ICVM" 1 ip bp len(memory) memory... len(input) input... len(output) output...
+//     version 2: "ICVM" 2 ip bp len(memory) len(dense) dense... len(sparse) (addr value)... input output
+//
+// Version 2 stores cells at huge addresses as address/value pairs. Both versions can be read.
+// Handlers of custom opcodes cannot be saved, so a loaded VM has the standard instruction set.
+pub static MAGIC: &[u8; 4] = b"ICVM";
+pub const VERSION: u8 = 2;
+
+//
+// enum SnapshotError
+//
+
+#[derive(Debug, thiserror::Error)]
+pub enum SnapshotError {
+    #[error("I/O error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Not a VM snapshot")]
+    BadMagic,
+    #[error("Unsupported snapshot version {0}")]
+    UnsupportedVersion(u8),
+    #[error("Number does not fit")]
+    Overflow,
+    #[error("Memory cells do not fit together")]
+    BadMemory,
+}
+
+//
+// struct Snapshot -- full VM state plus pending I/O
+//
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Snapshot {
+    pub vm: VM,
+    pub input: Vec<i128>,
+    pub output: Vec<i128>,
+}
+
+impl Snapshot {
+    pub fn new(vm: &VM, input: &[i128], output: &[i128]) -> Self {
+        Snapshot {
+            vm: vm.clone(),
+            input: input.to_vec(),
+            output: output.to_vec(),
+        }
+    }
+
+    pub fn write_to<W: Write>(&self, w: &mut W) -> std::io::Result<()> {
+        w.write_all(MAGIC)?;
+        w.write_all(&[VERSION])?;
+        write_varint(w, self.vm.ip as u128)?;
+        write_varint(w, zigzag(self.vm.bp))?;
+        let (dense, sparse) = self.vm.memory.parts();
+        let sparse = sparse.collect::<Vec<_>>();
+        write_varint(w, self.vm.memory.len() as u128)?;
+        write_values(w, dense.len(), dense.into_iter())?;
+        write_varint(w, sparse.len() as u128)?;
+        for (addr, value) in sparse {
+            write_varint(w, addr as u128)?;
+            write_varint(w, zigzag(value))?;
+        }
+        write_values(w, self.input.len(), self.input.iter().copied())?;
+        write_values(w, self.output.len(), self.output.iter().copied())
+    }
+
+    pub fn read_from<R: Read>(r: &mut R) -> Result<Self, SnapshotError> {
+        let mut magic = [0u8; 4];
+        r.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            return Err(SnapshotError::BadMagic);
+        }
+        let mut version = [0u8];
+        r.read_exact(&mut version)?;
+        if version[0] != 1 && version[0] != VERSION {
+            return Err(SnapshotError::UnsupportedVersion(version[0]));
+        }
+        let ip = read_usize(r)?;
+        let bp = unzigzag(read_varint(r)?);
+        let memory = if version[0] == 1 {
+            Memory::from(&read_values(r)?[..])
+        }
+        else {
+            let len = read_usize(r)?;
+            let dense = read_values(r)?;
+            let sparse = (0..read_usize(r)?)
+                .map(|_| Ok((read_usize(r)?, unzigzag(read_varint(r)?))))
+                .collect::<Result<Vec<_>, SnapshotError>>()?;
+            Memory::from_parts(len, dense, sparse).ok_or(SnapshotError::BadMemory)?
+        };
+        let input = read_values(r)?;
+        let output = read_values(r)?;
+        Ok(Snapshot { vm: VM { memory, ip, bp, isa: isa::STANDARD.clone() }, input, output })
+    }
+
+    pub fn save(&self, filename: &str) -> std::io::Result<()> {
+        let mut file = std::io::BufWriter::new(std::fs::File::create(filename)?);
+        self.write_to(&mut file)?;
+        file.flush()
+    }
+
+    pub fn load(filename: &str) -> Result<Self, SnapshotError> {
+        Snapshot::read_from(&mut std::io::BufReader::new(std::fs::File::open(filename)?))
+    }
+}
+
+//
+// encoding
+//
+
+fn zigzag(v: i128) -> u128 {
+    ((v << 1) ^ (v >> 127)) as u128
+}
+
+fn unzigzag(v: u128) -> i128 {
+    ((v >> 1) as i128) ^ -((v & 1) as i128)
+}
+
+fn write_varint<W: Write>(w: &mut W, mut v: u128) -> std::io::Result<()> {
+    loop {
+        let byte = (v & 0x7f) as u8;
+        v >>= 7;
+        if v == 0 {
+            return w.write_all(&[byte]);
+        }
+        w.write_all(&[byte | 0x80])?;
+    }
+}
+
+fn write_values<W: Write, I: Iterator<Item=i128>>(w: &mut W, len: usize, values: I) -> std::io::Result<()> {
+    write_varint(w, len as u128)?;
+    for v in values {
+        write_varint(w, zigzag(v))?;
+    }
+    Ok(())
+}
+
+fn read_varint<R: Read>(r: &mut R) -> Result<u128, SnapshotError> {
+    let mut result = 0u128;
+    for shift in (0..128).step_by(7) {
+        let mut byte = [0u8];
+        r.read_exact(&mut byte)?;
+        // the last group has room for only 2 of its 7 bits
+        let bits = (byte[0] & 0x7f) as u128;
+        if bits > u128::MAX >> shift {
+            return Err(SnapshotError::Overflow);
+        }
+        result |= bits << shift;
+        if byte[0] & 0x80 == 0 {
+            return Ok(result);
+        }
+    }
+    Err(SnapshotError::Overflow)
+}
+
+fn read_usize<R: Read>(r: &mut R) -> Result<usize, SnapshotError> {
+    usize::try_from(read_varint(r)?).map_err(|_| SnapshotError::Overflow)
+}
+
+fn read_values<R: Read>(r: &mut R) -> Result<Vec<i128>, SnapshotError> {
+    (0..read_usize(r)?).map(|_| read_varint(r).map(unzigzag)).collect()
+}
+
+//
+// tests
+//
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::intcode::IntcodeInput;
+    use crate::util;
+
+    fn roundtrip(snapshot: &Snapshot) -> Snapshot {
+        let mut bytes = vec![];
+        snapshot.write_to(&mut bytes).unwrap();
+        Snapshot::read_from(&mut &bytes[..]).unwrap()
+    }
+
+    #[test]
+    fn test_snapshot_zigzag() {
+        for &v in &[0, 1, -1, 63, -64, 1 << 70, i128::MAX, i128::MIN] {
+            assert_eq!(unzigzag(zigzag(v)), v);
+        }
+        assert_eq!(zigzag(-1), 1);
+        assert_eq!(zigzag(1), 2);
+    }
+
+    #[test]
+    fn test_snapshot_roundtrip() {
+        let mut vm = VM::new(&[109, -7, 3, 1000, 99, i128::MIN, i128::MAX]);
+        vm.ip = 2;
+        vm.bp = -7;
+        let snapshot = Snapshot::new(&vm, &[1, -2, 300], &[]);
+        assert_eq!(roundtrip(&snapshot), snapshot);
+    }
+
+    #[test]
+    fn test_snapshot_compact() {
+        let snapshot = Snapshot::new(&VM::new(&[1, 2, 3]), &[], &[-1]);
+        let mut bytes = vec![];
+        snapshot.write_to(&mut bytes).unwrap();
+        assert_eq!(bytes, b"ICVM\x02\x00\x00\x03\x03\x02\x04\x06\x00\x00\x01\x01");
+    }
+
+    #[test]
+    fn test_snapshot_version_1() {
+        let snapshot = Snapshot::read_from(&mut &b"ICVM\x01\x00\x00\x03\x02\x04\x06\x00\x01\x01"[..]).unwrap();
+        assert_eq!(snapshot, Snapshot::new(&VM::new(&[1, 2, 3]), &[], &[-1]));
+    }
+
+    #[test]
+    fn test_snapshot_sparse() {
+        let mut vm = VM::new(&[1101, 2, 3, 1 << 60, 99]);
+        vm.run(&mut &[][..]).unwrap();
+        let snapshot = Snapshot::new(&vm, &[], &[]);
+        let mut bytes = vec![];
+        snapshot.write_to(&mut bytes).unwrap();
+        assert!(bytes.len() < 64);
+        assert_eq!(Snapshot::read_from(&mut &bytes[..]).unwrap().vm.memory.load(1 << 60), 5);
+    }
+
+    #[test]
+    fn test_snapshot_resume() -> Result<(), Box<dyn std::error::Error>> {
+        // suspend day 9 halfway, restore, and finish the run
+        let mut vm = util::get_parsed_line::<VM>("input/day09.txt")?;
+        let mut input: &[i128] = &[2];
+        let mut output = vec![];
+        for _ in 0..1000 {
+            vm.tick(&mut || IntcodeInput::read(&mut input), &mut |v| output.push(v))?;
+        }
+        let mut snapshot = roundtrip(&Snapshot::new(&vm, input, &output));
+        snapshot.vm.run_with(&mut &snapshot.input[..], &mut snapshot.output)?;
+        assert_eq!(snapshot.output, &[73144]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_snapshot_errors() {
+        assert!(matches!(Snapshot::read_from(&mut &b"ICVX\x01"[..]), Err(SnapshotError::BadMagic)));
+        assert!(matches!(Snapshot::read_from(&mut &b"ICVM\x03"[..]), Err(SnapshotError::UnsupportedVersion(3))));
+        assert!(matches!(Snapshot::read_from(&mut &b"ICVM\x01\x00"[..]), Err(SnapshotError::Io(_))));
+        assert!(matches!(Snapshot::read_from(&mut &[&b"ICVM\x01"[..], &[0xff; 20]].concat()[..]), Err(SnapshotError::Overflow)));
+
+        // a sparse cell inside the pages, sparse cells out of order, or cells past the end
+        let corrupt = |memory: &[u8]| Snapshot::read_from(&mut &[&b"ICVM\x02\x00\x00"[..], memory, b"\x00\x00"].concat()[..]);
+        assert_eq!(corrupt(b"\x0a\x00\x01\x05\x02").unwrap().vm.memory.load(5), 1);
+        assert!(matches!(corrupt(b"\x0a\x01\x02\x01\x00\x02"), Err(SnapshotError::BadMemory)));
+        assert!(matches!(corrupt(b"\xd8\x04\x00\x02\x90\x03\x02\xac\x02\x02"), Err(SnapshotError::BadMemory)));
+        assert!(matches!(corrupt(b"\x01\x02\x02\x04\x00"), Err(SnapshotError::BadMemory)));
+        assert!(matches!(corrupt(b"\x0a\x00\x01\x0a\x02"), Err(SnapshotError::BadMemory)));
+
+        // u128::MAX takes 19 groups, and one bit more in the last group does not fit
+        let mut max = vec![];
+        write_varint(&mut max, u128::MAX).unwrap();
+        assert_eq!(max.len(), 19);
+        assert_eq!(read_varint(&mut &max[..]).unwrap(), u128::MAX);
+        max[18] = 0x04;
+        assert!(matches!(read_varint(&mut &max[..]), Err(SnapshotError::Overflow)));
+    }
+}
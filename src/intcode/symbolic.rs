@@ -0,0 +1,683 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::convert::TryFrom;
+use std::fmt;
+
+use crate::intcode::VmError;
+use crate::intcode::code::{Decoded, Op};
+
+// symbols without bounds of their own stay within +/- this, so that bounds cannot overflow
+pub const UNBOUNDED: i128 = 1 << 40;
+
+// nodes the solver visits before giving up on a set of constraints
+const SEARCH_LIMIT: usize = 100_000;
+
+// rounds of narrowing domains before the solver splits one instead
+const ROUNDS: usize = 64;
+
+// writes further than this past the program are not followed
+const MAX_MEMORY: usize = 1 << 20;
+
+//
+// struct Linear -- a constant plus a multiple of each symbol
+//
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Linear {
+    pub constant: i128,
+    // by symbol, never 0
+    pub terms: BTreeMap<usize, i128>,
+}
+
+impl Linear {
+    pub fn constant(value: i128) -> Self {
+        Linear { constant: value, terms: BTreeMap::new() }
+    }
+
+    pub fn symbol(symbol: usize) -> Self {
+        Linear { constant: 0, terms: std::iter::once((symbol, 1)).collect() }
+    }
+
+    pub fn as_constant(&self) -> Option<i128> {
+        if self.terms.is_empty() { Some(self.constant) } else { None }
+    }
+
+    // these return None on overflow
+    pub fn plus(&self, other: &Linear) -> Option<Linear> {
+        let mut result = self.clone();
+        result.constant = result.constant.checked_add(other.constant)?;
+        for (&symbol, &a) in &other.terms {
+            let sum = result.terms.get(&symbol).copied().unwrap_or(0).checked_add(a)?;
+            match sum {
+                0 => result.terms.remove(&symbol),
+                _ => result.terms.insert(symbol, sum),
+            };
+        }
+        Some(result)
+    }
+
+    pub fn times(&self, k: i128) -> Option<Linear> {
+        if k == 0 {
+            return Some(Linear::constant(0));
+        }
+        let terms = self.terms
+            .iter()
+            .map(|(&symbol, &a)| Some((symbol, a.checked_mul(k)?)))
+            .collect::<Option<_>>()?;
+        Some(Linear { constant: self.constant.checked_mul(k)?, terms })
+    }
+
+    pub fn minus(&self, other: &Linear) -> Option<Linear> {
+        self.plus(&other.times(-1)?)
+    }
+
+    pub fn eval(&self, values: &[i128]) -> Option<i128> {
+        self.terms
+            .iter()
+            .try_fold(self.constant, |sum, (&symbol, &a)| sum.checked_add(a.checked_mul(values[symbol])?))
+    }
+}
+
+//
+// struct Constraint -- a linear expression compared to 0
+//
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Rel {
+    Eq,
+    Ne,
+    Lt,
+    Ge,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Constraint {
+    pub expr: Linear,
+    pub rel: Rel,
+}
+
+impl Constraint {
+    pub fn holds(&self, values: &[i128]) -> bool {
+        match (self.expr.eval(values), self.rel) {
+            (Some(v), Rel::Eq) => v == 0,
+            (Some(v), Rel::Ne) => v != 0,
+            (Some(v), Rel::Lt) => v < 0,
+            (Some(v), Rel::Ge) => v >= 0,
+            (None, _)          => false,
+        }
+    }
+
+    pub fn negate(&self) -> Constraint {
+        let rel = match self.rel {
+            Rel::Eq => Rel::Ne,
+            Rel::Ne => Rel::Eq,
+            Rel::Lt => Rel::Ge,
+            Rel::Ge => Rel::Lt,
+        };
+        Constraint { expr: self.expr.clone(), rel }
+    }
+}
+
+//
+// solver
+//
+
+// Values within the domains, one per symbol, that satisfy all constraints. Domains are narrowed
+// by bounds propagation, then the smallest open one is split in two, depth first. None if there
+// is no solution, or if the search gives up; either way no path is known to get there.
+pub fn solve(constraints: &[Constraint], domains: &[(i128, i128)]) -> Option<Vec<i128>> {
+    // a sum of multiples of g only hits constants that g divides
+    for c in constraints.iter().filter(|c| c.rel == Rel::Eq) {
+        let g = c.expr.terms.values().fold(0, |g, &a| gcd(g, a));
+        if g != 0 && c.expr.constant % g != 0 {
+            return None;
+        }
+    }
+    let used = constraints.iter().flat_map(|c| c.expr.terms.keys().copied()).collect::<BTreeSet<_>>();
+    let mut nodes = 0;
+    search(constraints, &used, domains.to_vec(), &mut nodes)
+}
+
+fn search(constraints: &[Constraint], used: &BTreeSet<usize>, mut domains: Vec<(i128, i128)>, nodes: &mut usize) -> Option<Vec<i128>> {
+    *nodes += 1;
+    if *nodes > SEARCH_LIMIT || !propagate(constraints, &mut domains) {
+        return None;
+    }
+    let open = used
+        .iter()
+        .copied()
+        .filter(|&s| domains[s].0 < domains[s].1)
+        .min_by_key(|&s| domains[s].1 - domains[s].0);
+    match open {
+        // symbols no constraint mentions take the value closest to 0
+        None    => { let values = domains.iter().map(|&(lo, hi)| if used.is_empty() || lo == hi { lo } else { 0.max(lo).min(hi) }).collect::<Vec<_>>();
+                     if constraints.iter().all(|c| c.holds(&values)) { Some(values) } else { None } },
+        Some(s) => { let (lo, hi) = domains[s];
+                     let mid = lo + (hi - lo) / 2;
+                     let halves = if mid < 0 { [(mid + 1, hi), (lo, mid)] } else { [(lo, mid), (mid + 1, hi)] };
+                     halves.iter().find_map(|&half| {
+                         let mut domains = domains.clone();
+                         domains[s] = half;
+                         search(constraints, used, domains, nodes)
+                     }) },
+    }
+}
+
+// narrows the domains to the values that may still satisfy the constraints; false if one empties
+fn propagate(constraints: &[Constraint], domains: &mut [(i128, i128)]) -> bool {
+    for _ in 0..ROUNDS {
+        let mut changed = false;
+        for c in constraints {
+            // every relation but Ne is one or two of "expr <= 0"
+            let bounds = match c.rel {
+                Rel::Eq => vec![c.expr.clone(), c.expr.times(-1).unwrap_or_default()],
+                Rel::Lt => vec![c.expr.plus(&Linear::constant(1)).unwrap_or_default()],
+                Rel::Ge => vec![c.expr.times(-1).unwrap_or_default()],
+                Rel::Ne => vec![],
+            };
+            for expr in &bounds {
+                match at_most_zero(expr, domains) {
+                    Some(c) => changed |= c,
+                    None    => return false,
+                }
+            }
+            if c.rel == Rel::Ne {
+                match not_zero(&c.expr, domains) {
+                    Some(c) => changed |= c,
+                    None    => return false,
+                }
+            }
+        }
+        if !changed {
+            break;
+        }
+    }
+    true
+}
+
+// expr <= 0: each term is at most -(the least the other terms can add up to); None if impossible
+fn at_most_zero(expr: &Linear, domains: &mut [(i128, i128)]) -> Option<bool> {
+    let least = |a: i128, (lo, hi): (i128, i128)| if a > 0 { a.checked_mul(lo) } else { a.checked_mul(hi) };
+    let mut total = expr.constant;
+    for (&s, &a) in &expr.terms {
+        // too big to reason about, so leave it alone
+        match least(a, domains[s]).and_then(|t| total.checked_add(t)) {
+            Some(t) => total = t,
+            None    => return Some(false),
+        }
+    }
+    if total > 0 {
+        return None;
+    }
+    let mut changed = false;
+    for (&s, &a) in &expr.terms {
+        let (lo, hi) = domains[s];
+        let room = -(total - least(a, (lo, hi))?);
+        let bound = if a > 0 { (lo, hi.min(div_floor(room, a))) } else { (lo.max(div_ceil(room, a)), hi) };
+        if bound.0 > bound.1 {
+            return None;
+        }
+        changed |= bound != (lo, hi);
+        domains[s] = bound;
+    }
+    Some(changed)
+}
+
+// expr != 0: only decides anything once at most one symbol is open, and its bad value is a bound
+fn not_zero(expr: &Linear, domains: &mut [(i128, i128)]) -> Option<bool> {
+    let mut rest = expr.constant;
+    let mut open = None;
+    for (&s, &a) in &expr.terms {
+        match domains[s] {
+            (lo, hi) if lo == hi => match a.checked_mul(lo).and_then(|t| rest.checked_add(t)) {
+                                        Some(sum) => rest = sum,
+                                        None      => return Some(false),
+                                    },
+            _ if open.is_none()  => open = Some((s, a)),
+            _                    => return Some(false),
+        }
+    }
+    match open {
+        None                          => if rest == 0 { None } else { Some(false) },
+        Some((s, a)) if rest % a == 0 => { let bad = -rest / a;
+                                           let (lo, hi) = domains[s];
+                                           domains[s] = match bad {
+                                               _ if bad == lo => (lo + 1, hi),
+                                               _ if bad == hi => (lo, hi - 1),
+                                               _              => return Some(false),
+                                           };
+                                           Some(true) },
+        Some(_)                       => Some(false),
+    }
+}
+
+fn gcd(a: i128, b: i128) -> i128 {
+    if b == 0 { a.abs() } else { gcd(b, a % b) }
+}
+
+fn div_floor(a: i128, b: i128) -> i128 {
+    let q = a / b;
+    if a % b != 0 && (a < 0) != (b < 0) { q - 1 } else { q }
+}
+
+fn div_ceil(a: i128, b: i128) -> i128 {
+    let q = a / b;
+    if a % b != 0 && (a < 0) == (b < 0) { q + 1 } else { q }
+}
+
+//
+// struct Path -- one way through the program, and what it takes to go that way
+//
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum End {
+    Halted,
+    // wants more input than the explorer gives
+    Starved,
+    Crash(VmError),
+    // depends on something the explorer cannot follow, like an unknown jump target
+    Unsupported(&'static str),
+    OutOfBudget,
+}
+
+impl fmt::Display for End {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            End::Halted         => write!(f, "halted"),
+            End::Starved        => write!(f, "waiting for input"),
+            End::Crash(e)       => write!(f, "{}", e),
+            End::Unsupported(s) => write!(f, "gave up on {}", s),
+            End::OutOfBudget    => write!(f, "ran out of budget"),
+        }
+    }
+}
+
+// Values are None where they are not linear in the symbols: products of symbols, and reads from an
+// address that depends on one. That is fine as long as they do not decide anything.
+#[derive(Debug, Clone)]
+pub struct Path {
+    pub constraints: Vec<Constraint>,
+    pub outputs: Vec<Option<Linear>>,
+    pub memory: Vec<Option<Linear>>,
+    // how many values it read
+    pub inputs: usize,
+    pub end: End,
+}
+
+#[derive(Clone)]
+struct State {
+    ip: usize,
+    bp: i128,
+    memory: Vec<Option<Linear>>,
+    inputs: usize,
+    outputs: Vec<Option<Linear>>,
+    constraints: Vec<Constraint>,
+    steps: u64,
+}
+
+//
+// struct Solution
+//
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Solution {
+    // symbolic cells, by address
+    pub cells: Vec<(usize, i128)>,
+    // in the order the program reads them
+    pub inputs: Vec<i128>,
+}
+
+impl fmt::Display for Solution {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (addr, value) in &self.cells {
+            writeln!(f, "[{}] = {}", addr, value)?;
+        }
+        writeln!(f, "input {}", self.inputs.iter().map(|v| v.to_string()).collect::<Vec<_>>().join(","))
+    }
+}
+
+//
+// struct Explorer -- runs a program on symbols instead of numbers, down every path it can take
+//
+
+// Symbols are the cells made symbolic, in order, followed by one per input value. Comparisons and
+// branches on symbols fork the path, each side with the constraint that leads there, as long as the
+// solver finds that side feasible.
+pub struct Explorer {
+    image: Vec<i128>,
+    // address and domain
+    cells: Vec<(usize, (i128, i128))>,
+    pub max_inputs: usize,
+    pub input_domain: (i128, i128),
+    // instructions per path
+    pub budget: u64,
+    pub max_paths: usize,
+}
+
+impl Explorer {
+    pub fn new(memory: &[i128]) -> Self {
+        Explorer {
+            image: memory.to_vec(),
+            cells: vec![],
+            max_inputs: 8,
+            input_domain: (-UNBOUNDED, UNBOUNDED),
+            budget: 100_000,
+            max_paths: 1000,
+        }
+    }
+
+    // makes a cell of the program a symbol, with a value in lo..=hi
+    pub fn symbolic_cell(&mut self, addr: usize, lo: i128, hi: i128) {
+        self.cells.push((addr, (lo, hi)));
+    }
+
+    fn domains(&self) -> Vec<(i128, i128)> {
+        self.cells
+            .iter()
+            .map(|&(_, domain)| domain)
+            .chain(std::iter::repeat_n(self.input_domain, self.max_inputs))
+            .collect()
+    }
+
+    pub fn explore(&self) -> Vec<Path> {
+        let domains = self.domains();
+        let mut memory = self.image.iter().map(|&v| Some(Linear::constant(v))).collect::<Vec<_>>();
+        for (symbol, &(addr, _)) in self.cells.iter().enumerate() {
+            if addr >= memory.len() {
+                memory.resize(addr + 1, Some(Linear::constant(0)));
+            }
+            memory[addr] = Some(Linear::symbol(symbol));
+        }
+
+        let mut stack = vec![State { ip: 0, bp: 0, memory, inputs: 0, outputs: vec![], constraints: vec![], steps: 0 }];
+        let mut paths = vec![];
+        while let Some(mut state) = stack.pop() {
+            if paths.len() == self.max_paths {
+                break;
+            }
+            let end = loop {
+                if state.steps == self.budget {
+                    break End::OutOfBudget;
+                }
+                state.steps += 1;
+                if let Err(end) = self.step(&mut state, &domains, &mut stack) {
+                    break end;
+                }
+            };
+            paths.push(Path { constraints: state.constraints, outputs: state.outputs, memory: state.memory, inputs: state.inputs, end });
+        }
+        paths
+    }
+
+    // symbol values for the first path whose last output can be the target
+    pub fn solve_output(&self, paths: &[Path], target: i128) -> Option<Solution> {
+        self.solve(paths, target, |path| path.outputs.last()?.as_ref())
+    }
+
+    // symbol values for the first path that halts with the target in the cell
+    pub fn solve_cell(&self, paths: &[Path], addr: usize, target: i128) -> Option<Solution> {
+        self.solve(paths, target, |path| if path.end == End::Halted { path.memory.get(addr)?.as_ref() } else { None })
+    }
+
+    fn solve<F>(&self, paths: &[Path], target: i128, pick: F) -> Option<Solution>
+        where
+            F: Fn(&Path) -> Option<&Linear>
+    {
+        let domains = self.domains();
+        paths.iter().find_map(|path| {
+            let mut constraints = path.constraints.clone();
+            constraints.push(Constraint { expr: pick(path)?.minus(&Linear::constant(target))?, rel: Rel::Eq });
+            let values = solve(&constraints, &domains)?;
+            Some(Solution {
+                cells: self.cells.iter().zip(&values).map(|(&(addr, _), &v)| (addr, v)).collect(),
+                inputs: values[self.cells.len()..self.cells.len() + path.inputs].to_vec(),
+            })
+        })
+    }
+
+    //
+    // execution
+    //
+
+    fn cell(state: &State, addr: usize) -> Option<Linear> {
+        state.memory.get(addr).cloned().unwrap_or_else(|| Some(Linear::constant(0)))
+    }
+
+    // None if the address depends on a symbol
+    fn addr(state: &State, d: Decoded, instr: i128, i: usize) -> Result<Option<usize>, End> {
+        let base = match d.modes[i - 1] {
+            0 => 0,
+            1 => return Ok(Some(state.ip + i)),
+            2 => state.bp,
+            m => return Err(End::Crash(VmError::BadMode { ip: state.ip, bp: state.bp, instr, param: i, mode: m as i128 })),
+        };
+        let addr = match Explorer::cell(state, state.ip + i).and_then(|v| v.as_constant()) {
+            Some(v) => v.checked_add(base).ok_or(End::Crash(VmError::Overflow { ip: state.ip, bp: state.bp, instr }))?,
+            None    => return Ok(None),
+        };
+        match usize::try_from(addr) {
+            Ok(addr) => Ok(Some(addr)),
+            Err(_)   => Err(End::Crash(VmError::NegativeAddress { ip: state.ip, bp: state.bp, instr, addr })),
+        }
+    }
+
+    fn read(state: &State, d: Decoded, instr: i128, i: usize) -> Result<Option<Linear>, End> {
+        Ok(Explorer::addr(state, d, instr, i)?.and_then(|addr| Explorer::cell(state, addr)))
+    }
+
+    fn dst(state: &State, d: Decoded, instr: i128, i: usize) -> Result<usize, End> {
+        if d.modes[i - 1] == 1 {
+            return Err(End::Crash(VmError::WriteToImmediate { ip: state.ip, bp: state.bp, instr, param: i }));
+        }
+        match Explorer::addr(state, d, instr, i)? {
+            Some(addr) if addr < self::MAX_MEMORY => Ok(addr),
+            Some(_)                               => Err(End::Unsupported("write far past the program")),
+            None                                  => Err(End::Unsupported("write to an address that depends on a symbol")),
+        }
+    }
+
+    fn store(state: &mut State, addr: usize, value: Option<Linear>) {
+        if addr >= state.memory.len() {
+            state.memory.resize(addr + 1, Some(Linear::constant(0)));
+        }
+        state.memory[addr] = value;
+    }
+
+    // whether the constraint holds on this path, and the other side too if that is feasible as well
+    fn fork(state: &mut State, c: Constraint, domains: &[(i128, i128)]) -> Result<(bool, Option<State>), End> {
+        if c.expr.as_constant().is_some() {
+            return Ok((c.holds(&[]), None));
+        }
+        let feasible = |c: &Constraint| {
+            let mut constraints = state.constraints.clone();
+            constraints.push(c.clone());
+            solve(&constraints, domains).is_some()
+        };
+        let other = c.negate();
+        let (yes, no) = (feasible(&c), feasible(&other));
+        let mut fork = None;
+        if yes && no {
+            let mut state = state.clone();
+            state.constraints.push(other);
+            fork = Some(state);
+        }
+        match (yes, no) {
+            (true, _)      => { state.constraints.push(c); Ok((true, fork)) },
+            (false, true)  => { state.constraints.push(c.negate()); Ok((false, None)) },
+            (false, false) => Err(End::Unsupported("the solver gave up")),
+        }
+    }
+
+    // executes one instruction; Err once the path ends
+    fn step(&self, state: &mut State, domains: &[(i128, i128)], stack: &mut Vec<State>) -> Result<(), End> {
+        let instr = Explorer::cell(state, state.ip)
+            .and_then(|v| v.as_constant())
+            .ok_or(End::Unsupported("an instruction that depends on a symbol"))?;
+        let d = Decoded::new(instr);
+        let read = |state: &State, i| Explorer::read(state, d, instr, i);
+        match d.op {
+            Op::Add | Op::Mul => { let (a, b) = (read(state, 1)?, read(state, 2)?);
+                                   let dst = Explorer::dst(state, d, instr, 3)?;
+                                   let value = match (a, b) {
+                                       (Some(a), Some(b)) if d.op == Op::Add => a.plus(&b),
+                                       (Some(a), Some(b))                    => match (a.as_constant(), b.as_constant()) {
+                                           (Some(k), _) => b.times(k),
+                                           (_, Some(k)) => a.times(k),
+                                           _            => None,
+                                       },
+                                       _                                     => None,
+                                   };
+                                   Explorer::store(state, dst, value);
+                                   state.ip += 4; },
+
+            Op::In            => { let dst = Explorer::dst(state, d, instr, 1)?;
+                                   if state.inputs == self.max_inputs {
+                                       return Err(End::Starved);
+                                   }
+                                   Explorer::store(state, dst, Some(Linear::symbol(self.cells.len() + state.inputs)));
+                                   state.inputs += 1;
+                                   state.ip += 2; },
+
+            Op::Out           => { let value = read(state, 1)?;
+                                   state.outputs.push(value);
+                                   state.ip += 2; },
+
+            Op::Jnz | Op::Jz  => { let cond = read(state, 1)?.ok_or(End::Unsupported("a branch on an unknown value"))?;
+                                   let target = read(state, 2)?
+                                       .and_then(|t| t.as_constant())
+                                       .ok_or(End::Unsupported("a jump to an address that depends on a symbol"))?;
+                                   let target = usize::try_from(target)
+                                       .map_err(|_| End::Crash(VmError::NegativeAddress { ip: state.ip, bp: state.bp, instr, addr: target }))?;
+                                   let next = state.ip + 3;
+                                   let jumps = |nonzero: bool| if nonzero == (d.op == Op::Jnz) { target } else { next };
+                                   let (nonzero, other) = Explorer::fork(state, Constraint { expr: cond, rel: Rel::Ne }, domains)?;
+                                   if let Some(mut other) = other {
+                                       other.ip = jumps(!nonzero);
+                                       stack.push(other);
+                                   }
+                                   state.ip = jumps(nonzero); },
+
+            Op::Lt | Op::Eq   => { let (a, b) = (read(state, 1)?, read(state, 2)?);
+                                   let dst = Explorer::dst(state, d, instr, 3)?;
+                                   match a.zip(b).and_then(|(a, b)| a.minus(&b)) {
+                                       Some(diff) => { let rel = if d.op == Op::Lt { Rel::Lt } else { Rel::Eq };
+                                                       let (holds, other) = Explorer::fork(state, Constraint { expr: diff, rel }, domains)?;
+                                                       if let Some(mut other) = other {
+                                                           Explorer::store(&mut other, dst, Some(Linear::constant(!holds as i128)));
+                                                           other.ip += 4;
+                                                           stack.push(other);
+                                                       }
+                                                       Explorer::store(state, dst, Some(Linear::constant(holds as i128))); },
+                                       None       => Explorer::store(state, dst, None),
+                                   }
+                                   state.ip += 4; },
+
+            Op::Bp            => { let offset = read(state, 1)?
+                                       .and_then(|v| v.as_constant())
+                                       .ok_or(End::Unsupported("a relative base that depends on a symbol"))?;
+                                   state.bp = state.bp.checked_add(offset).ok_or(End::Crash(VmError::Overflow { ip: state.ip, bp: state.bp, instr }))?;
+                                   state.ip += 2; },
+
+            Op::Halt          => return Err(End::Halted),
+
+            Op::Unknown       => return Err(End::Crash(VmError::UnknownOpcode { ip: state.ip, bp: state.bp, instr })),
+        }
+        Ok(())
+    }
+}
+
+//
+// tests
+//
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::intcode::{asm, VM};
+    use crate::util;
+
+    fn linear(constant: i128, terms: &[(usize, i128)]) -> Linear {
+        Linear { constant, terms: terms.iter().copied().collect() }
+    }
+
+    #[test]
+    fn test_symbolic_solve() {
+        let c = |constant, terms: &[(usize, i128)], rel| Constraint { expr: linear(constant, terms), rel };
+        let domains = [(0, 99), (0, 99)];
+
+        // 3x + y == 200, x < y
+        let constraints = [c(-200, &[(0, 3), (1, 1)], Rel::Eq), c(0, &[(0, 1), (1, -1)], Rel::Lt)];
+        let values = solve(&constraints, &domains).unwrap();
+        assert!(constraints.iter().all(|c| c.holds(&values)), "{:?}", values);
+
+        // 2x + 4y == 7 has no integer solution, and neither does x != 5 with x == 5
+        assert_eq!(solve(&[c(-7, &[(0, 2), (1, 4)], Rel::Eq)], &domains), None);
+        assert_eq!(solve(&[c(-5, &[(0, 1)], Rel::Ne), c(-5, &[(0, 1)], Rel::Ge), c(-6, &[(0, 1)], Rel::Lt)], &domains), None);
+
+        // x + y >= 150 and x - y >= 90 cannot fit in 0..=99
+        assert_eq!(solve(&[c(-150, &[(0, 1), (1, 1)], Rel::Ge), c(-90, &[(0, 1), (1, -1)], Rel::Ge)], &domains), None);
+        assert_eq!(solve(&[c(-108, &[(0, 1), (1, 1)], Rel::Ge), c(-90, &[(0, 1), (1, -1)], Rel::Ge)], &domains), Some(vec![99, 9]));
+    }
+
+    #[test]
+    fn test_symbolic_paths() {
+        // prints 2a + 1 if the input is below 10, 0 if it is 42, and 100 otherwise
+        let vm = asm::assemble("
+                   IN   [a]
+                   LT   [a], 10, [t]
+                   JZ   [t], big
+                   MUL  [a], 2, [t]
+                   ADD  [t], 1, [t]
+                   OUT  [t]
+                   HLT
+            big:   EQ   [a], 42, [t]
+                   JNZ  [t], zero
+                   OUT  100
+                   HLT
+            zero:  OUT  0
+                   HLT
+            a:     DW   0
+            t:     DW   0
+        ").unwrap();
+        let explorer = Explorer::new(&vm.memory.to_vec());
+        let paths = explorer.explore();
+        assert_eq!(paths.len(), 3);
+        assert!(paths.iter().all(|path| path.end == End::Halted && path.inputs == 1));
+
+        assert_eq!(explorer.solve_output(&paths, 15).map(|s| s.inputs), Some(vec![7]));
+        assert_eq!(explorer.solve_output(&paths, 0).map(|s| s.inputs), Some(vec![42]));
+        assert_eq!(explorer.solve_output(&paths, 16), None);
+        assert_eq!(explorer.solve_output(&paths, 21), None);
+        let other = explorer.solve_output(&paths, 100).unwrap().inputs[0];
+        assert!(other >= 10 && other != 42, "{}", other);
+
+        // reading more than it may ends the path
+        let mut explorer = Explorer::new(&[3, 0, 3, 0, 99]);
+        explorer.max_inputs = 1;
+        assert_eq!(explorer.explore()[0].end, End::Starved);
+
+        // so does jumping to where a symbol says
+        let explorer = Explorer::new(&[3, 4, 1105, 1, 0, 99]);
+        assert_eq!(explorer.explore()[0].end, End::Unsupported("a jump to an address that depends on a symbol"));
+    }
+
+    #[test]
+    fn test_symbolic_puzzles() -> Result<(), Box<dyn std::error::Error>> {
+        // BOOST runs its self test on 1; the keycode it prints gives that input back
+        let vm = util::get_parsed_line::<VM>("input/day09.txt")?;
+        let explorer = Explorer::new(&vm.memory.to_vec());
+        let paths = explorer.explore();
+        let keycode = VM::new(&vm.memory.to_vec()).run(&mut &[1][..])?[0];
+        assert_eq!(explorer.solve_output(&paths, keycode).unwrap().inputs, [1]);
+
+        // noun and verb for day 2 part 1
+        let vm = util::get_parsed_line::<VM>("input/day02.txt")?;
+        let mut explorer = Explorer::new(&vm.memory.to_vec());
+        explorer.symbolic_cell(1, 0, 99);
+        explorer.symbolic_cell(2, 0, 99);
+        let solution = explorer.solve_cell(&explorer.explore(), 0, 4930687).unwrap();
+        assert_eq!(solution.cells, [(1, 12), (2, 2)]);
+        assert_eq!(solution.to_string(), "[1] = 12\n[2] = 2\ninput \n");
+        Ok(())
+    }
+}
@@ -0,0 +1,235 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::io::Write;
+
+use crate::intcode::{Instruction, IntcodeInput, Mode, Status, VM, VmError};
+
+// number of rows in the hot address and hot block tables
+const TOP: usize = 20;
+
+//
+// trait Tracer
+//
+
+// Called before each instruction is executed. The operand values are the values read by the
+// instruction, except for the parameter it writes to, which holds the target address instead.
+pub trait Tracer {
+    fn trace(&mut self, vm: &VM, instr: &Instruction, values: &[i128]);
+}
+
+impl VM {
+    // like tick, but reports the decoded instruction to the tracer first
+    pub fn tick_traced<T, R, W>(&mut self, tracer: &mut T, read: &mut R, write: &mut W) -> Result<Option<Status>, VmError>
+        where
+            T: Tracer + ?Sized,
+            R: FnMut() -> Option<i128>,
+            W: FnMut(i128)
+    {
+        if let Some(instr) = self.executing() {
+            let d = self.memory.decoded(self.ip);
+            let values = (1..=instr.params.len())
+                .map(|i| match self.addr(d, i) {
+                    Ok(addr) if instr.op.writes == Some(i) => addr as i128,
+                    Ok(addr)                               => self.load(addr),
+                    Err(_)                                 => instr.params[i - 1],
+                })
+                .collect::<Vec<_>>();
+            tracer.trace(self, &instr, &values);
+        }
+        self.tick(read, write)
+    }
+
+    pub fn run_traced<I, T>(&mut self, input: &mut I, tracer: &mut T) -> Result<Vec<i128>, VmError>
+        where
+            I: IntcodeInput + ?Sized,
+            T: Tracer + ?Sized
+    {
+        let mut output = vec![];
+        loop {
+            let status = self.tick_traced(tracer, &mut || input.read(), &mut |v| output.push(v))?;
+            match status {
+                Some(Status::Halted)  => break,
+                Some(Status::Blocked) => return Err(VmError::InputStarved { ip: self.ip, bp: self.bp, instr: self.load(self.ip) }),
+                _                     => continue,
+            }
+        }
+        Ok(output)
+    }
+}
+
+//
+// struct FileTracer -- writes one line per executed instruction
+//
+
+pub struct FileTracer<W: Write> {
+    out: W,
+    pub error: Option<std::io::Error>,
+}
+
+impl<W: Write> FileTracer<W> {
+    pub fn new(out: W) -> Self {
+        FileTracer { out, error: None }
+    }
+
+    pub fn finish(mut self) -> std::io::Result<W> {
+        match self.error {
+            Some(e) => Err(e),
+            None    => { self.out.flush()?; Ok(self.out) },
+        }
+    }
+}
+
+impl<W: Write> Tracer for FileTracer<W> {
+    fn trace(&mut self, vm: &VM, instr: &Instruction, values: &[i128]) {
+        if self.error.is_some() {
+            return;
+        }
+        let values = values
+            .iter()
+            .enumerate()
+            .map(|(i, v)| if instr.op.writes == Some(i + 1) { format!("@{}", v) } else { v.to_string() })
+            .collect::<Vec<_>>()
+            .join(" ");
+        let modes = instr.modes
+            .iter()
+            .map(|mode| match mode { Mode::Position => 'P', Mode::Immediate => 'I', Mode::Relative => 'R' })
+            .collect::<String>();
+        if let Err(e) = writeln!(self.out, "{:6} {:4} {:3} {:<3} {:<32} {}", vm.ip, vm.bp, instr.op.code, modes, instr.to_string(), values) {
+            self.error = Some(e);
+        }
+    }
+}
+
+//
+// struct Profiler -- aggregates executed instructions
+//
+
+#[derive(Debug, Default)]
+pub struct Profiler {
+    pub total: u64,
+    pub addresses: HashMap<usize, u64>,
+    pub opcodes: BTreeMap<&'static str, u64>,
+    pub blocks: HashMap<usize, u64>,
+    code: HashMap<usize, String>,
+    next: Option<usize>,
+}
+
+impl Profiler {
+    pub fn new() -> Self {
+        Profiler::default()
+    }
+
+    fn top(counts: &HashMap<usize, u64>) -> Vec<(usize, u64)> {
+        let mut result = counts.iter().map(|(&a, &n)| (a, n)).collect::<Vec<_>>();
+        result.sort_by_key(|&(a, n)| (std::cmp::Reverse(n), a));
+        result.truncate(TOP);
+        result
+    }
+}
+
+impl Tracer for Profiler {
+    fn trace(&mut self, _vm: &VM, instr: &Instruction, _values: &[i128]) {
+        self.total += 1;
+        *self.addresses.entry(instr.addr).or_insert(0) += 1;
+        *self.opcodes.entry(instr.op.name).or_insert(0) += 1;
+
+        // a basic block starts wherever execution did not simply fall through from a non-jump
+        if self.next != Some(instr.addr) {
+            *self.blocks.entry(instr.addr).or_insert(0) += 1;
+        }
+        self.next = if instr.op.jumps.is_some() { None } else { Some(instr.end()) };
+
+        self.code.entry(instr.addr).or_insert_with(|| instr.to_string());
+    }
+}
+
+impl fmt::Display for Profiler {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let percent = |n: u64| 100.0 * n as f64 / self.total.max(1) as f64;
+
+        writeln!(f, "instructions executed: {}", self.total)?;
+        writeln!(f, "basic blocks entered:  {}", self.blocks.values().sum::<u64>())?;
+
+        writeln!(f, "\n--- opcodes")?;
+        let mut opcodes = self.opcodes.iter().collect::<Vec<_>>();
+        opcodes.sort_by_key(|&(name, &n)| (std::cmp::Reverse(n), *name));
+        for (name, &n) in opcodes {
+            writeln!(f, "{:<4} {:>12} {:6.2}%", name, n, percent(n))?;
+        }
+
+        writeln!(f, "\n--- hot addresses")?;
+        for (addr, n) in Profiler::top(&self.addresses) {
+            writeln!(f, "{:6} {:>12} {:6.2}%  {}", addr, n, percent(n), self.code[&addr])?;
+        }
+
+        writeln!(f, "\n--- hot blocks")?;
+        for (addr, n) in Profiler::top(&self.blocks) {
+            writeln!(f, "{:6} {:>12}  {}", addr, n, self.code[&addr])?;
+        }
+        Ok(())
+    }
+}
+
+//
+// tests
+//
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::util;
+
+    #[test]
+    fn test_trace_file() -> Result<(), Box<dyn std::error::Error>> {
+        let mut vm = VM::new(&[109, 6, 202, 1, 8, 9, 99, 23, 42, 0]);
+        let mut tracer = FileTracer::new(vec![]);
+        vm.run_traced(&mut &[][..], &mut tracer)?;
+        let text = String::from_utf8(tracer.finish()?)?;
+        let lines = text.lines().map(|line| line.split_whitespace().collect::<Vec<_>>().join(" ")).collect::<Vec<_>>();
+        assert_eq!(lines, [
+            "0 0 9 I BP 6 6",
+            "2 6 2 RPP MUL [bp+1], [8], [9] 23 42 @9",
+            "6 6 99 HLT",
+        ]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_trace_profile() -> Result<(), Box<dyn std::error::Error>> {
+        // day 9 example 1 loops 16 times over a single block
+        let mut vm = VM::new(&[109, 1, 204, -1, 1001, 100, 1, 100, 1008, 100, 16, 101, 1006, 101, 0, 99]);
+        let mut profiler = Profiler::new();
+        let output = vm.run_traced(&mut &[][..], &mut profiler)?;
+        assert_eq!(output.len(), 16);
+        assert_eq!(profiler.total, 16 * 5 + 1);
+        assert_eq!(profiler.opcodes["OUT"], 16);
+        assert_eq!(profiler.addresses[&0], 16);
+        assert_eq!(profiler.blocks[&0], 16);
+        assert_eq!(profiler.blocks[&15], 1);
+        assert_eq!(profiler.blocks.len(), 2);
+        Ok(())
+    }
+
+    #[test]
+    fn test_trace_high_digits() -> Result<(), VmError> {
+        // runs as ADD [5], [5], [5], ignoring the 1 above the modes, then halts
+        let mut vm = VM::new(&[100001, 5, 5, 5, 99, 3]);
+        let mut profiler = Profiler::new();
+        vm.run_traced(&mut &[][..], &mut profiler)?;
+        assert_eq!(vm.memory[5], 6);
+        assert_eq!(profiler.total, 2);
+        assert_eq!(profiler.opcodes["ADD"], 1);
+        Ok(())
+    }
+
+    #[test]
+    fn test_trace_matches_run() -> Result<(), Box<dyn std::error::Error>> {
+        let vm = util::get_parsed_line::<VM>("input/day09.txt")?;
+        let mut profiler = Profiler::new();
+        assert_eq!(vm.clone().run_traced(&mut &[1][..], &mut profiler)?, vm.clone().run(&mut &[1][..])?);
+        assert_eq!(profiler.addresses.values().sum::<u64>(), profiler.total);
+        assert!(profiler.to_string().starts_with(&format!("instructions executed: {}", profiler.total)));
+        Ok(())
+    }
+}
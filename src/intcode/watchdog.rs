@@ -0,0 +1,235 @@
+use std::collections::{HashMap, HashSet};
+use std::time::{Duration, Instant};
+
+use crate::intcode::{IntcodeInput, Status, VM, VmError, Word};
+use crate::intcode::code::Op;
+
+// how many instructions run between looks at the clock
+const CLOCK_INTERVAL: u64 = 1024;
+
+//
+// struct Cycles -- notices when the whole machine state comes back with no I/O in between
+//
+
+// Brent's method: the state at a checkpoint is compared to every later state, and the checkpoint
+// moves to the current state after 1, 2, 4, ... instructions. Instead of hashing all of memory,
+// only the cells written since the checkpoint are compared, so each instruction costs O(1).
+#[derive(Debug, Clone)]
+struct Cycles<T: Word> {
+    ip: usize,
+    bp: i128,
+    // values at the checkpoint of the cells written since
+    original: HashMap<usize, T>,
+    // the cells among those that hold a different value now
+    changed: HashSet<usize>,
+    steps: u64,
+    power: u64,
+}
+
+impl<T: Word> Cycles<T> {
+    fn new(vm: &VM<T>) -> Self {
+        Cycles { ip: vm.ip, bp: vm.bp, original: HashMap::new(), changed: HashSet::new(), steps: 0, power: 1 }
+    }
+
+    fn checkpoint(&mut self, vm: &VM<T>) {
+        self.ip = vm.ip;
+        self.bp = vm.bp;
+        self.original.clear();
+        self.changed.clear();
+        self.steps = 0;
+    }
+
+    // the period, if the state after an instruction that wrote to addr is the checkpoint state
+    fn step(&mut self, vm: &VM<T>, addr: Option<usize>) -> Option<u64> {
+        if let Some(addr) = addr {
+            let value = vm.load(addr);
+            if *self.original.get(&addr).unwrap_or(&value) == value {
+                self.changed.remove(&addr);
+            } else {
+                self.changed.insert(addr);
+            }
+        }
+        self.steps += 1;
+        if vm.ip == self.ip && vm.bp == self.bp && self.changed.is_empty() {
+            return Some(self.steps);
+        }
+        if self.steps == self.power {
+            self.power *= 2;
+            self.checkpoint(vm);
+        }
+        None
+    }
+}
+
+//
+// struct Watchdog -- stops a VM that runs too long, or in circles
+//
+
+#[derive(Debug, Clone)]
+pub struct Watchdog<T: Word = i128> {
+    pub budget: Option<u64>,
+    pub deadline: Option<Instant>,
+    // instructions executed so far
+    pub executed: u64,
+    cycles: Option<Cycles<T>>,
+    detect_cycles: bool,
+}
+
+impl<T: Word> Default for Watchdog<T> {
+    fn default() -> Self {
+        Watchdog { budget: None, deadline: None, executed: 0, cycles: None, detect_cycles: false }
+    }
+}
+
+impl<T: Word> Watchdog<T> {
+    pub fn new() -> Self {
+        Watchdog::default()
+    }
+
+    pub fn with_budget(mut self, budget: u64) -> Self {
+        self.budget = Some(budget);
+        self
+    }
+
+    // the deadline is this long from now
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.deadline = Some(Instant::now() + timeout);
+        self
+    }
+
+    pub fn detecting_cycles(mut self) -> Self {
+        self.detect_cycles = true;
+        self
+    }
+
+    // runs one tick of the VM, after checking the limits, and looks for a cycle after it
+    pub fn watch<F>(&mut self, vm: &mut VM<T>, tick: F) -> Result<Option<Status>, VmError>
+        where
+            F: FnOnce(&mut VM<T>) -> Result<Option<Status>, VmError>
+    {
+        if self.budget.is_some_and(|budget| self.executed >= budget) {
+            return Err(VmError::BudgetExceeded { ip: vm.ip, bp: vm.bp, instr: vm.instr(), budget: self.executed });
+        }
+        if self.executed.is_multiple_of(CLOCK_INTERVAL) && self.deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+            return Err(VmError::DeadlinePassed { ip: vm.ip, bp: vm.bp, instr: vm.instr(), executed: self.executed });
+        }
+
+        let d = vm.memory.decoded(vm.ip);
+        let written = match d.op {
+            Op::Add | Op::Mul | Op::Lt | Op::Eq => vm.addr(d, 3).ok(),
+            Op::In                              => vm.addr(d, 1).ok(),
+            _                                   => None,
+        };
+        if let (Some(cycles), Some(addr)) = (self.cycles.as_mut(), written) {
+            cycles.original.entry(addr).or_insert_with(|| vm.load(addr));
+        }
+
+        let status = tick(vm)?;
+        self.executed += 1;
+
+        if self.detect_cycles {
+            // input, output and added opcodes start the search over, as they may talk to the host;
+            // a halted or blocked VM stays put, and is no cycle
+            let io = matches!(d.op, Op::In | Op::Out | Op::Unknown);
+            match self.cycles.as_mut() {
+                Some(cycles) if !io && status.is_none() => if let Some(period) = cycles.step(vm, written) {
+                                                               return Err(VmError::Cycle { ip: vm.ip, bp: vm.bp, instr: vm.instr(), period });
+                                                           },
+                _                                       => self.cycles = Some(Cycles::new(vm)),
+            }
+        }
+        Ok(status)
+    }
+}
+
+impl<T: Word> VM<T> {
+    // like tick, but fails once the watchdog's limits are exceeded
+    pub fn tick_watched<R, W>(&mut self, watchdog: &mut Watchdog<T>, read: &mut R, write: &mut W) -> Result<Option<Status>, VmError>
+        where
+            R: FnMut() -> Option<T>,
+            W: FnMut(T)
+    {
+        watchdog.watch(self, |vm| vm.tick(read, write))
+    }
+
+    pub fn run_watched<I>(&mut self, input: &mut I, watchdog: &mut Watchdog<T>) -> Result<Vec<T>, VmError>
+        where
+            I: IntcodeInput<T> + ?Sized
+    {
+        let mut output = vec![];
+        loop {
+            let status = self.tick_watched(watchdog, &mut || input.read(), &mut |v| output.push(v))?;
+            match status {
+                Some(Status::Halted)  => break,
+                Some(Status::Blocked) => return Err(VmError::InputStarved { ip: self.ip, bp: self.bp, instr: self.instr() }),
+                _                     => continue,
+            }
+        }
+        Ok(output)
+    }
+}
+
+//
+// tests
+//
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::intcode::asm;
+    use crate::util;
+
+    #[test]
+    fn test_watchdog_budget() {
+        let mut vm = VM::new(&[1105, 1, 0]);
+        let mut watchdog = Watchdog::new().with_budget(10);
+        assert_eq!(vm.run_watched(&mut &[][..], &mut watchdog), Err(VmError::BudgetExceeded { ip: 0, bp: 0, instr: 1105, budget: 10 }));
+        assert_eq!(watchdog.executed, 10);
+    }
+
+    #[test]
+    fn test_watchdog_deadline() {
+        let mut vm = VM::new(&[1105, 1, 0]);
+        let mut watchdog = Watchdog::new().with_timeout(Duration::from_millis(10));
+        match vm.run_watched(&mut &[][..], &mut watchdog) {
+            Err(VmError::DeadlinePassed { executed, .. }) => assert!(executed > 0 && executed.is_multiple_of(CLOCK_INTERVAL)),
+            wtf                                           => panic!("{:?}", wtf),
+        }
+    }
+
+    #[test]
+    fn test_watchdog_cycles() -> Result<(), Box<dyn std::error::Error>> {
+        // sets a flag back and forth, so the state repeats every 5 instructions
+        let mut vm = asm::assemble("
+            loop: ADD  [f], 1, [f]
+                  ADD  [f], -1, [f]
+                  BP   1
+                  BP   -1
+                  JNZ  1, loop
+            f:    DW   0
+        ").unwrap();
+        match vm.run_watched(&mut &[][..], &mut Watchdog::new().detecting_cycles()) {
+            Err(VmError::Cycle { period, .. }) => assert_eq!(period, 5),
+            wtf                                => panic!("{:?}", wtf),
+        }
+
+        // a counter never repeats, and output in between starts the search over
+        let mut counter = asm::assemble("
+            loop: ADD  [n], 1, [n]
+                  JNZ  1, loop
+            n:    DW   0
+        ").unwrap();
+        let mut watchdog = Watchdog::new().with_budget(10_000).detecting_cycles();
+        assert!(matches!(counter.run_watched(&mut &[][..], &mut watchdog), Err(VmError::BudgetExceeded { .. })));
+        let mut printer = VM::new(&[104, 1, 1105, 1, 0]);
+        let mut watchdog = Watchdog::new().with_budget(10_000).detecting_cycles();
+        assert!(matches!(printer.run_watched(&mut &[][..], &mut watchdog), Err(VmError::BudgetExceeded { .. })));
+
+        // puzzles that halt run to the end
+        let mut day09 = util::get_parsed_line::<VM>("input/day09.txt")?;
+        let mut watchdog = Watchdog::new().with_budget(1_000_000).detecting_cycles();
+        assert_eq!(day09.run_watched(&mut &[1][..], &mut watchdog)?.len(), 1);
+        Ok(())
+    }
+}
@@ -0,0 +1,94 @@
+use std::convert::TryFrom;
+use std::fmt;
+use std::str::FromStr;
+
+use num_bigint::BigInt;
+use num_traits::{ToPrimitive, Zero};
+
+//
+// trait Word -- the type of a memory cell
+//
+
+// Arithmetic is checked: None means the result does not fit, which the VM reports as an error
+// instead of wrapping or panicking.
+pub trait Word: Clone + Default + PartialEq + PartialOrd + fmt::Debug + fmt::Display + FromStr + Send + Sync + 'static {
+    // the name used to select this word type on the command line
+    const NAME: &'static str;
+
+    fn from_i128(value: i128) -> Option<Self>;
+    fn to_i128(&self) -> Option<i128>;
+    fn checked_add(&self, other: &Self) -> Option<Self>;
+    fn checked_mul(&self, other: &Self) -> Option<Self>;
+
+    fn is_zero(&self) -> bool {
+        *self == Self::default()
+    }
+
+    fn from_bool(value: bool) -> Self {
+        Self::from_i128(value as i128).unwrap()
+    }
+}
+
+impl Word for i64 {
+    const NAME: &'static str = "i64";
+
+    fn from_i128(value: i128) -> Option<Self> {
+        i64::try_from(value).ok()
+    }
+
+    fn to_i128(&self) -> Option<i128> {
+        Some(*self as i128)
+    }
+
+    fn checked_add(&self, other: &Self) -> Option<Self> {
+        i64::checked_add(*self, *other)
+    }
+
+    fn checked_mul(&self, other: &Self) -> Option<Self> {
+        i64::checked_mul(*self, *other)
+    }
+}
+
+impl Word for i128 {
+    const NAME: &'static str = "i128";
+
+    fn from_i128(value: i128) -> Option<Self> {
+        Some(value)
+    }
+
+    fn to_i128(&self) -> Option<i128> {
+        Some(*self)
+    }
+
+    fn checked_add(&self, other: &Self) -> Option<Self> {
+        i128::checked_add(*self, *other)
+    }
+
+    fn checked_mul(&self, other: &Self) -> Option<Self> {
+        i128::checked_mul(*self, *other)
+    }
+}
+
+impl Word for BigInt {
+    const NAME: &'static str = "big";
+
+    fn from_i128(value: i128) -> Option<Self> {
+        Some(BigInt::from(value))
+    }
+
+    fn to_i128(&self) -> Option<i128> {
+        ToPrimitive::to_i128(self)
+    }
+
+    fn checked_add(&self, other: &Self) -> Option<Self> {
+        Some(self + other)
+    }
+
+    fn checked_mul(&self, other: &Self) -> Option<Self> {
+        Some(self * other)
+    }
+
+    fn is_zero(&self) -> bool {
+        Zero::is_zero(self)
+    }
+}
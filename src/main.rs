@@ -30,6 +30,8 @@ mod day25;
 
 extern crate itertools;
 extern crate num_complex;
+extern crate num_bigint;
+extern crate num_traits;
 extern crate permutohedron;
 extern crate bytecount;
 extern crate thiserror;
@@ -37,8 +39,16 @@ extern crate regex;
 
 #[macro_use] extern crate lazy_static;
 
+use std::convert::TryFrom;
 use std::fmt::Display;
+use std::collections::VecDeque;
 use std::error::Error;
+use std::io::BufRead;
+use std::sync::Arc;
+
+use itertools::Itertools;
+
+use intcode::Word;
 
 fn format_thousands(n: u128) -> String {
     if n > 0 { format!("{} {:3}", format_thousands(n / 1000), n % 1000) } else { "".to_owned() }
@@ -135,21 +145,84 @@ fn puzzles() -> Result<(), Box<dyn Error>> {
     puzzle(24, 'a', Box::new(util::get_parsed::<day24::Bugs>), Box::new(day24::day24a))?;
     puzzle(24, 'b', Box::new(util::get_parsed::<day24::Bugs>), Box::new(day24::day24b))?;
 
+    puzzle(25, 'a', Box::new(util::get_parsed_line::<intcode::VM>), Box::new(day25::day25a))?;
+
     println!("--- ---------------- ---------------");
 
     Ok(())
 }
 
+// runs any Intcode program with the given word type, printing its output; the watchdog stops it if stuck
+fn run_program<T: intcode::Word>(filename: &str, input: Option<String>, isa: Arc<intcode::InstructionSet>, budget: Option<u64>, timeout: Option<f64>, cycles: bool) -> Result<(), Box<dyn Error>> {
+    let mut watchdog = intcode::watchdog::Watchdog::new();
+    watchdog.budget = budget;
+    if let Some(seconds) = timeout {
+        watchdog = watchdog.with_timeout(std::time::Duration::from_secs_f64(seconds));
+    }
+    if cycles {
+        watchdog = watchdog.detecting_cycles();
+    }
+    let mut vm = util::get_parsed_line::<intcode::VM<T>>(filename)?.with_isa(isa);
+    let mut input = input
+        .map_or(Ok(VecDeque::new()), |s| s.split(',').map(|v| v.trim().parse::<T>().map_err(|_| intcode::InputError::Parse(v.trim().to_owned()))).collect())?;
+    let output = vm.run_watched(&mut input, &mut watchdog)?;
+    println!("{}", output.iter().map(|v| v.to_string()).collect::<Vec<_>>().join(","));
+    Ok(())
+}
+
+// a VM from a snapshot file, or else from a program
+fn load_vm(filename: &str) -> Result<intcode::VM, Box<dyn Error>> {
+    match intcode::snapshot::Snapshot::load(filename) {
+        Ok(snapshot)                                    => Ok(snapshot.vm),
+        Err(intcode::snapshot::SnapshotError::BadMagic) => Ok(util::get_parsed_line(filename)?),
+        Err(e)                                          => Err(e.into()),
+    }
+}
+
+// the positional argument at i, counting from the one after the command
+fn arg<'a>(args: &'a [String], i: usize, what: &'static str) -> Result<&'a str, ArgumentError> {
+    args.get(i).map(String::as_str).ok_or(ArgumentError::MissingArgument(what))
+}
+
+// a comma-separated list of numbers, or none if the argument is not there
+fn numbers(arg: Option<&String>) -> Result<Vec<i128>, std::num::ParseIntError> {
+    arg.map_or(Ok(vec![]), |s| s.split(',').map(|v| v.trim().parse::<i128>()).collect())
+}
+
+// removes a flag and the value after it from the arguments
+fn take_option(args: &mut Vec<String>, flag: &str, what: &'static str) -> Result<Option<String>, ArgumentError> {
+    match args.iter().position(|a| a == flag) {
+        Some(i) if i + 1 < args.len() => { args.remove(i); Ok(Some(args.remove(i))) },
+        Some(_)                       => Err(ArgumentError::MissingArgument(what)),
+        None                          => Ok(None),
+    }
+}
+
+// removes a flag from the arguments, and tells whether it was there
+fn take_flag(args: &mut Vec<String>, flag: &str) -> bool {
+    match args.iter().position(|a| a == flag) {
+        Some(i) => { args.remove(i); true },
+        None    => false,
+    }
+}
+
 #[derive(Debug, thiserror::Error)]
 enum ArgumentError {
     #[error("Argument not understood: {0:?}")]
-    BadArgument(String)
+    BadArgument(String),
+    #[error("Missing argument: {0}")]
+    MissingArgument(&'static str),
 }
 
 fn main() -> Result<(), Box<dyn Error>> {
-    let arg = std::env::args().nth(1);
+    // every command takes its options out of args first, so that what is left is positional
+    let command = std::env::args().nth(1);
+    let mut args = std::env::args().skip(2).collect::<Vec<_>>();
+    // --extended anywhere among the arguments adds the opcodes of isa::extended
+    let extended = take_flag(&mut args, "--extended");
+    let isa = Arc::new(if extended { intcode::isa::extended() } else { intcode::InstructionSet::standard() });
 
-    match arg.as_deref() {
+    match command.as_deref() {
 
         Some("8b")  => { let input = util::get_parsed_line::<day08::Input>("input/day08.txt")?;
                          day08::day08_main(&input); },
@@ -169,8 +242,164 @@ fn main() -> Result<(), Box<dyn Error>> {
         Some("18")  => { let input = util::get_parsed::<day18::Maze>("input/day18.txt")?;
                          day18::day18_main(&input)?; },
 
-        Some("25")  => { let input = util::get_parsed_line::<intcode::VM>("input/day25.txt")?;
-                         day25::day25_main(&input)?; },
+        Some("25")  => { let snapshot = match take_option(&mut args, "--resume", "<snapshot file>")? {
+                             Some(filename) => intcode::snapshot::Snapshot::load(&filename)?,
+                             None           => intcode::snapshot::Snapshot::new(&util::get_parsed_line::<intcode::VM>("input/day25.txt")?, &[], &[]),
+                         };
+                         let script = match take_option(&mut args, "--script", "<script file>")? {
+                             Some(filename) => day25::parse_script(&util::get_text(&filename)?),
+                             None           => vec![],
+                         };
+                         let mut record = match take_option(&mut args, "--record", "<script file>")? {
+                             Some(filename) => Some(std::fs::OpenOptions::new().create(true).append(true).open(filename)?),
+                             None           => None,
+                         };
+                         day25::day25_main(&snapshot, &script, record.as_mut().map(|f| f as &mut dyn std::io::Write))?; },
+
+        Some("disasm") => { let vm = util::get_parsed_line::<intcode::VM>(arg(&args, 0, "<file>")?)?.with_isa(isa.clone());
+                            print!("{}", intcode::disasm::Listing::from(&vm)); },
+
+        Some("cfg") => { let vm = util::get_parsed_line::<intcode::VM>(arg(&args, 0, "<file>")?)?.with_isa(isa.clone());
+                         print!("{}", intcode::cfg::Cfg::from(&vm).dot()); },
+
+        Some("decompile") => { let vm = util::get_parsed_line::<intcode::VM>(arg(&args, 0, "<file>")?)?.with_isa(isa.clone());
+                               print!("{}", intcode::decompile::Program::from(&vm)); },
+
+        Some("asm") => { let source = util::get_text(arg(&args, 0, "<file>")?)?;
+                         let vm = if extended { intcode::asm::assemble_with(&source, isa)? } else { intcode::asm::assemble(&source)? };
+                         println!("{}", vm.memory.iter().map(|v| v.to_string()).collect::<Vec<_>>().join(",")); },
+
+        Some("debug") => { let mut debugger = match take_option(&mut args, "--resume", "<snapshot file>")? {
+                               Some(filename) => intcode::debug::Debugger::from_snapshot(intcode::snapshot::Snapshot::load(&filename)?),
+                               None           => intcode::debug::Debugger::new(util::get_parsed_line::<intcode::VM>(arg(&args, 0, "<file>")?)?),
+                           };
+                           debugger.vm = debugger.vm.with_isa(isa.clone());
+                           debugger.repl(std::io::stdin().lock(), std::io::stdout())?; },
+
+        Some("trace") => { let vm = util::get_parsed_line::<intcode::VM>(arg(&args, 0, "<file>")?)?.with_isa(isa.clone());
+                           let mut tracer = intcode::trace::FileTracer::new(std::io::BufWriter::new(std::fs::File::create(arg(&args, 1, "<trace file>")?)?));
+                           let output = vm.clone().run_traced(&mut &numbers(args.get(2))?[..], &mut tracer)?;
+                           tracer.finish()?;
+                           println!("{:?}", output); },
+
+        Some("profile") => { let vm = util::get_parsed_line::<intcode::VM>(arg(&args, 0, "<file>")?)?.with_isa(isa.clone());
+                             let mut profiler = intcode::trace::Profiler::new();
+                             let output = vm.clone().run_traced(&mut &numbers(args.get(1))?[..], &mut profiler)?;
+                             println!("output: {:?}\n", output);
+                             print!("{}", profiler); },
+
+        Some("heatmap") => { let ppm = take_option(&mut args, "--ppm", "<image file>")?;
+                             let mut vm = util::get_parsed_line::<intcode::VM>(arg(&args, 0, "<file>")?)?.with_isa(isa.clone());
+                             let input = numbers(args.get(1))?;
+                             let mut heatmap = intcode::heatmap::Heatmap::default();
+                             // a program waiting for more input still shows what it did so far
+                             match vm.run_traced(&mut &input[..], &mut heatmap) {
+                                 Ok(_) | Err(intcode::VmError::InputStarved { .. }) => (),
+                                 Err(e)                                             => return Err(e.into()),
+                             }
+                             print!("{}", heatmap.render(64));
+                             if let Some(filename) = ppm {
+                                 heatmap.write_ppm(&mut std::io::BufWriter::new(std::fs::File::create(filename)?))?;
+                             } },
+
+        Some("diff") => { let diff = intcode::diff::Diff::new(&load_vm(arg(&args, 0, "<file>")?)?, &load_vm(arg(&args, 1, "<file>")?)?);
+                          match diff.is_empty() {
+                              true  => println!("no differences"),
+                              false => print!("{}", diff),
+                          } },
+
+        Some("check") => { let vm = util::get_parsed_line::<intcode::VM>(arg(&args, 0, "<file>")?)?.with_isa(isa.clone());
+                           let mut checker = intcode::check::Checker::new(&vm);
+                           let output = vm.clone().run_traced(&mut &numbers(args.get(1))?[..], &mut checker)?;
+                           println!("output: {:?}\n", output);
+                           print!("{}", checker); },
+
+        Some("fuzz") => { let vm = util::get_parsed_line::<intcode::VM>(arg(&args, 0, "<file>")?)?.with_isa(isa.clone());
+                          let runs = args.get(1).map_or(Ok(10_000), |s| s.parse::<u64>())?;
+                          let mut fuzzer = intcode::fuzz::Fuzzer::new(&vm, 1);
+                          fuzzer.fuzz(runs);
+                          print!("{}", fuzzer); },
+
+        Some("solve") => { // --cells makes cells of the program unknowns too, and --at solves for a cell instead of the output
+                           let cells = numbers(take_option(&mut args, "--cells", "<addresses>")?.as_ref())?;
+                           let range = take_option(&mut args, "--range", "<lo,hi>")?;
+                           let at = take_option(&mut args, "--at", "<address>")?.map(|s| s.parse::<usize>()).transpose()?;
+                           let vm = util::get_parsed_line::<intcode::VM>(arg(&args, 0, "<file>")?)?;
+                           let target = arg(&args, 1, "<value>")?.parse::<i128>()?;
+                           let mut explorer = intcode::symbolic::Explorer::new(&vm.memory.to_vec());
+                           let (lo, hi) = match numbers(range.as_ref())?[..] {
+                               [lo, hi] => (lo, hi),
+                               []       => explorer.input_domain,
+                               _        => return Err(ArgumentError::BadArgument(range.unwrap_or_default()).into()),
+                           };
+                           for &addr in &cells {
+                               explorer.symbolic_cell(usize::try_from(addr)?, lo, hi);
+                           }
+                           let paths = explorer.explore();
+                           println!("{} paths explored", paths.len());
+                           for (count, end) in paths.iter().map(|path| path.end.to_string()).sorted().dedup_with_count() {
+                               println!("{:>6}x  {}", count, end);
+                           }
+                           let solution = match at {
+                               Some(addr) => explorer.solve_cell(&paths, addr, target),
+                               None       => explorer.solve_output(&paths, target),
+                           };
+                           match solution {
+                               Some(solution) => print!("{}", solution),
+                               None           => println!("no solution found that gives {}", target),
+                           } },
+
+        Some("run") => { let word = take_option(&mut args, "--word", "<word>")?.unwrap_or_else(|| i128::NAME.to_owned());
+                         let budget = take_option(&mut args, "--budget", "<instructions>")?.map(|s| s.parse::<u64>()).transpose()?;
+                         let timeout = take_option(&mut args, "--timeout", "<seconds>")?.map(|s| s.parse::<f64>()).transpose()?;
+                         let cycles = take_flag(&mut args, "--cycles");
+                         let filename = arg(&args, 0, "<file>")?;
+                         let input = args.get(1).cloned();
+                         match word.as_str() {
+                             w if w == i64::NAME                => run_program::<i64>(filename, input, isa, budget, timeout, cycles)?,
+                             w if w == i128::NAME               => run_program::<i128>(filename, input, isa, budget, timeout, cycles)?,
+                             w if w == num_bigint::BigInt::NAME => run_program::<num_bigint::BigInt>(filename, input, isa, budget, timeout, cycles)?,
+                             w                                  => return Err(ArgumentError::BadArgument(w.to_owned()).into()),
+                         } },
+
+        Some("network") => { let vm = util::get_parsed_line::<intcode::VM>(arg(&args, 0, "<file>")?)?.with_isa(isa.clone());
+                             let topology = arg(&args, 1, "<topology>")?.parse()?;
+                             let count = arg(&args, 2, "<count>")?.parse::<i128>()?;
+                             let input = numbers(args.get(3))?;
+                             let machines = (0..count).map(|i| (vm.clone(), if i == 0 { [&[i], &input[..]].concat() } else { vec![i] })).collect();
+                             let network = intcode::runtime::Network::start(topology, machines);
+                             // when the network is stuck, more input for the first machine comes from stdin
+                             let mut lines = std::io::stdin().lock().lines();
+                             loop {
+                                 while let Some(event) = network.next_event() {
+                                     println!("{:?}", event);
+                                 }
+                                 match lines.next().transpose()? {
+                                     Some(line) => network.send(0, &line.split(',').map(|v| v.trim().parse::<i128>()).collect::<Result<Vec<_>, _>>()?),
+                                     None       => break,
+                                 }
+                             } },
+
+        Some("packets") => { let replay = take_option(&mut args, "--replay", "<log file>")?;
+                             let vm = util::get_parsed_line::<intcode::VM>(arg(&args, 0, "<file>")?)?.with_isa(isa.clone());
+                             if let Some(filename) = replay {
+                                 let log = util::get_text(&filename)?.lines().map(|line| line.parse()).collect::<Result<Vec<_>, _>>()?;
+                                 match intcode::packet::replay(&vm, intcode::packet::Config::default(), &log)? {
+                                     Some(d) => println!("Differs at packet {}: expected {:?}, got {:?}", d.index, d.expected, d.actual),
+                                     None    => println!("Replayed {} packets", log.len()),
+                                 }
+                             }
+                             else {
+                                 let mut log = match args.get(1) {
+                                     Some(filename) => Box::new(std::io::BufWriter::new(std::fs::File::create(filename)?)) as Box<dyn std::io::Write>,
+                                     None           => Box::new(std::io::sink()),
+                                 };
+                                 let mut nat = day23::Monitor::default();
+                                 let mut network = intcode::packet::PacketNetwork::new(&vm, intcode::packet::Config::default()).with_log(&mut log);
+                                 network.run(&mut nat)?;
+                                 print!("{}", network.stats);
+                                 println!("NAT stopped at y = {:?}", nat.y);
+                             } },
 
         Some(x)     => { return Err(ArgumentError::BadArgument(x.to_owned()).into()); },
 